@@ -0,0 +1,349 @@
+use xcb;
+use icccm;
+use ewmh;
+
+/// Client window properties a window manager or taskbar usually reacts to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClientProperty {
+	/// `WM_NAME`
+	WmName,
+
+	/// `_NET_WM_NAME`
+	NetWmName,
+
+	/// `WM_HINTS`
+	WmHints,
+
+	/// `WM_NORMAL_HINTS`
+	WmNormalHints,
+
+	/// `_NET_WM_STATE`
+	NetWmState,
+
+	/// `_NET_WM_ICON`
+	NetWmIcon,
+
+	/// `_NET_WM_STRUT_PARTIAL`
+	NetWmStrutPartial,
+}
+
+pub enum ClientPropertyCookie<'a> {
+	WmName(icccm::GetTextPropertyCookie<'a>),
+	NetWmName(ewmh::GetWmNameCookie<'a>),
+	WmHints(icccm::GetWmHintsCookie<'a>),
+	WmNormalHints(icccm::GetWmSizeHintsCookie<'a>),
+	NetWmState(ewmh::GetWmStateCookie<'a>),
+	NetWmIcon(ewmh::GetWmIconCookie<'a>),
+	NetWmStrutPartial(ewmh::GetWmStrutPartialCookie<'a>),
+}
+
+pub enum ClientPropertyReply {
+	WmName(icccm::GetTextPropertyReply),
+	NetWmName(ewmh::GetWmNameReply),
+	WmHints(icccm::WmHints),
+	WmNormalHints(icccm::SizeHints),
+	NetWmState(ewmh::GetWmStateReply),
+	NetWmIcon(ewmh::GetWmIconReply),
+	NetWmStrutPartial(ewmh::StrutPartial),
+}
+
+impl<'a> ClientPropertyCookie<'a> {
+	pub fn property(&self) -> ClientProperty {
+		match *self {
+			ClientPropertyCookie::WmName(..)            => ClientProperty::WmName,
+			ClientPropertyCookie::NetWmName(..)         => ClientProperty::NetWmName,
+			ClientPropertyCookie::WmHints(..)           => ClientProperty::WmHints,
+			ClientPropertyCookie::WmNormalHints(..)     => ClientProperty::WmNormalHints,
+			ClientPropertyCookie::NetWmState(..)        => ClientProperty::NetWmState,
+			ClientPropertyCookie::NetWmIcon(..)         => ClientProperty::NetWmIcon,
+			ClientPropertyCookie::NetWmStrutPartial(..) => ClientProperty::NetWmStrutPartial,
+		}
+	}
+
+	pub fn get_reply(&self) -> Result<ClientPropertyReply, xcb::ReplyError> {
+		Ok(match *self {
+			ClientPropertyCookie::WmName(ref cookie) =>
+				ClientPropertyReply::WmName(cookie.get_reply()?),
+
+			ClientPropertyCookie::NetWmName(ref cookie) =>
+				ClientPropertyReply::NetWmName(cookie.get_reply()?),
+
+			ClientPropertyCookie::WmHints(ref cookie) =>
+				ClientPropertyReply::WmHints(cookie.get_reply()?),
+
+			ClientPropertyCookie::WmNormalHints(ref cookie) =>
+				ClientPropertyReply::WmNormalHints(cookie.get_reply()?),
+
+			ClientPropertyCookie::NetWmState(ref cookie) =>
+				ClientPropertyReply::NetWmState(cookie.get_reply()?),
+
+			ClientPropertyCookie::NetWmIcon(ref cookie) =>
+				ClientPropertyReply::NetWmIcon(cookie.get_reply()?),
+
+			ClientPropertyCookie::NetWmStrutPartial(ref cookie) =>
+				ClientPropertyReply::NetWmStrutPartial(cookie.get_reply()?),
+		})
+	}
+}
+
+impl ewmh::Connection {
+	/// Maps a property atom, usually from a `PropertyNotify` event, to the
+	/// client property it names.
+	pub fn classify_property(&self, atom: xcb::Atom) -> Option<ClientProperty> {
+		match atom {
+			xcb::ATOM_WM_NAME =>
+				Some(ClientProperty::WmName),
+
+			xcb::ATOM_WM_HINTS =>
+				Some(ClientProperty::WmHints),
+
+			xcb::ATOM_WM_NORMAL_HINTS =>
+				Some(ClientProperty::WmNormalHints),
+
+			atom if atom == self.WM_NAME() =>
+				Some(ClientProperty::NetWmName),
+
+			atom if atom == self.WM_STATE() =>
+				Some(ClientProperty::NetWmState),
+
+			atom if atom == self.WM_ICON() =>
+				Some(ClientProperty::NetWmIcon),
+
+			atom if atom == self.WM_STRUT_PARTIAL() =>
+				Some(ClientProperty::NetWmStrutPartial),
+
+			_ =>
+				None
+		}
+	}
+
+	/// Requests the current value of the given property.
+	pub fn fetch_property(&self, window: xcb::Window, property: ClientProperty) -> ClientPropertyCookie {
+		match property {
+			ClientProperty::WmName =>
+				ClientPropertyCookie::WmName(icccm::get_wm_name(self, window)),
+
+			ClientProperty::NetWmName =>
+				ClientPropertyCookie::NetWmName(ewmh::get_wm_name(self, window)),
+
+			ClientProperty::WmHints =>
+				ClientPropertyCookie::WmHints(icccm::get_wm_hints(self, window)),
+
+			ClientProperty::WmNormalHints =>
+				ClientPropertyCookie::WmNormalHints(icccm::get_wm_normal_hints(self, window)),
+
+			ClientProperty::NetWmState =>
+				ClientPropertyCookie::NetWmState(ewmh::get_wm_state(self, window)),
+
+			ClientProperty::NetWmIcon =>
+				ClientPropertyCookie::NetWmIcon(ewmh::get_wm_icon(self, window)),
+
+			ClientProperty::NetWmStrutPartial =>
+				ClientPropertyCookie::NetWmStrutPartial(ewmh::get_wm_strut_partial(self, window)),
+		}
+	}
+
+	/// Classifies the changed property and requests its new value, `None` if
+	/// the property is not a known client property.
+	pub fn refetch_property(&self, event: &xcb::PropertyNotifyEvent) -> Option<ClientPropertyCookie> {
+		self.classify_property(event.atom())
+			.map(|property| self.fetch_property(event.window(), property))
+	}
+}
+
+/// A snapshot of the ICCCM and EWMH properties of a client window.
+pub struct ClientInfo {
+	window: xcb::Window,
+
+	class:         Option<(String, String)>,
+	wm_name:       Option<String>,
+	net_wm_name:   Option<String>,
+	wm_icon_name:  Option<String>,
+	net_icon_name: Option<String>,
+	hints:         Option<icccm::WmHints>,
+	normal_hints:  Option<icccm::SizeHints>,
+	transient_for: Option<xcb::Window>,
+	protocols:     Vec<xcb::Atom>,
+	pid:           Option<u32>,
+	desktop:       Option<u32>,
+	window_type:   Vec<xcb::Atom>,
+	state:         Vec<xcb::Atom>,
+	strut:         Option<ewmh::Extents>,
+	strut_partial: Option<ewmh::StrutPartial>,
+	frame_extents: Option<ewmh::Extents>,
+	icon:          Option<ewmh::GetWmIconReply>,
+}
+
+struct ClientInfoCookies<'a> {
+	window: xcb::Window,
+
+	class:         icccm::GetWmClassCookie<'a>,
+	wm_name:       icccm::GetTextPropertyCookie<'a>,
+	net_wm_name:   ewmh::GetWmNameCookie<'a>,
+	wm_icon_name:  icccm::GetTextPropertyCookie<'a>,
+	net_icon_name: ewmh::GetWmIconNameCookie<'a>,
+	hints:         icccm::GetWmHintsCookie<'a>,
+	normal_hints:  icccm::GetWmSizeHintsCookie<'a>,
+	transient_for: icccm::GetWmTransientForCookie<'a>,
+	protocols:     icccm::GetWmProtocolsCookie<'a>,
+	pid:           ewmh::GetWmPidCookie<'a>,
+	desktop:       ewmh::GetWmDesktopCookie<'a>,
+	window_type:   ewmh::GetWmWindowTypeCookie<'a>,
+	state:         ewmh::GetWmStateCookie<'a>,
+	strut:         ewmh::GetWmStrutCookie<'a>,
+	strut_partial: ewmh::GetWmStrutPartialCookie<'a>,
+	frame_extents: ewmh::GetFrameExtentsCookie<'a>,
+	icon:          ewmh::GetWmIconCookie<'a>,
+}
+
+impl<'a> ClientInfoCookies<'a> {
+	fn request(c: &'a ewmh::Connection, window: xcb::Window) -> Self {
+		ClientInfoCookies {
+			window: window,
+
+			class:         icccm::get_wm_class_unchecked(c, window),
+			wm_name:       icccm::get_wm_name_unchecked(c, window),
+			net_wm_name:   ewmh::get_wm_name_unchecked(c, window),
+			wm_icon_name:  icccm::get_wm_icon_name_unchecked(c, window),
+			net_icon_name: ewmh::get_wm_icon_name_unchecked(c, window),
+			hints:         icccm::get_wm_hints_unchecked(c, window),
+			normal_hints:  icccm::get_wm_normal_hints_unchecked(c, window),
+			transient_for: icccm::get_wm_transient_for_unchecked(c, window),
+			protocols:     icccm::get_wm_protocols_unchecked(c, window, c.WM_PROTOCOLS()),
+			pid:           ewmh::get_wm_pid_unchecked(c, window),
+			desktop:       ewmh::get_wm_desktop_unchecked(c, window),
+			window_type:   ewmh::get_wm_window_type_unchecked(c, window),
+			state:         ewmh::get_wm_state_unchecked(c, window),
+			strut:         ewmh::get_wm_strut_unchecked(c, window),
+			strut_partial: ewmh::get_wm_strut_partial_unchecked(c, window),
+			frame_extents: ewmh::get_frame_extents_unchecked(c, window),
+			icon:          ewmh::get_wm_icon_unchecked(c, window),
+		}
+	}
+
+	fn collect(self) -> ClientInfo {
+		ClientInfo {
+			window: self.window,
+
+			class:         self.class.get_reply().ok()
+				.map(|r| (r.instance().to_owned(), r.class().to_owned())),
+			wm_name:       self.wm_name.get_reply().ok().map(|r| r.name().to_owned()),
+			net_wm_name:   self.net_wm_name.get_reply().ok().map(|r| r.string().to_owned()),
+			wm_icon_name:  self.wm_icon_name.get_reply().ok().map(|r| r.name().to_owned()),
+			net_icon_name: self.net_icon_name.get_reply().ok().map(|r| r.string().to_owned()),
+			hints:         self.hints.get_reply().ok(),
+			normal_hints:  self.normal_hints.get_reply().ok(),
+			transient_for: self.transient_for.get_reply().ok(),
+			protocols:     self.protocols.get_reply().map(|r| r.atoms().to_vec()).unwrap_or_default(),
+			pid:           self.pid.get_reply().ok(),
+			desktop:       self.desktop.get_reply().ok(),
+			window_type:   self.window_type.get_reply().map(|r| r.atoms().to_vec()).unwrap_or_default(),
+			state:         self.state.get_reply().map(|r| r.atoms().to_vec()).unwrap_or_default(),
+			strut:         self.strut.get_reply().ok(),
+			strut_partial: self.strut_partial.get_reply().ok(),
+			frame_extents: self.frame_extents.get_reply().ok(),
+			icon:          self.icon.get_reply().ok(),
+		}
+	}
+}
+
+impl ClientInfo {
+	/// Fetches every property of the window in a single round trip, missing
+	/// properties are left empty.
+	pub fn fetch(c: &ewmh::Connection, window: xcb::Window) -> ClientInfo {
+		ClientInfoCookies::request(c, window).collect()
+	}
+
+	/// Fetches the properties of all the given windows in a single round trip.
+	pub fn fetch_many(c: &ewmh::Connection, windows: &[xcb::Window]) -> Vec<ClientInfo> {
+		let cookies = windows.iter()
+			.map(|&window| ClientInfoCookies::request(c, window))
+			.collect::<Vec<_>>();
+
+		cookies.into_iter().map(|cookies| cookies.collect()).collect()
+	}
+
+	/// Fetches the properties of every window in `_NET_CLIENT_LIST`.
+	pub fn fetch_all(c: &ewmh::Connection, screen: ewmh::Screen) -> Result<Vec<ClientInfo>, xcb::ReplyError> {
+		let list = ewmh::get_client_list(c, screen).get_reply()?;
+		Ok(ClientInfo::fetch_many(c, list.windows()))
+	}
+
+	pub fn window(&self) -> xcb::Window {
+		self.window
+	}
+
+	pub fn instance(&self) -> Option<&str> {
+		self.class.as_ref().map(|class| class.0.as_ref())
+	}
+
+	pub fn class(&self) -> Option<&str> {
+		self.class.as_ref().map(|class| class.1.as_ref())
+	}
+
+	/// The `_NET_WM_NAME` falling back to `WM_NAME`.
+	pub fn name(&self) -> Option<&str> {
+		self.net_wm_name.as_ref().or(self.wm_name.as_ref()).map(|n| n.as_ref())
+	}
+
+	pub fn wm_name(&self) -> Option<&str> {
+		self.wm_name.as_ref().map(|n| n.as_ref())
+	}
+
+	pub fn net_wm_name(&self) -> Option<&str> {
+		self.net_wm_name.as_ref().map(|n| n.as_ref())
+	}
+
+	/// The `_NET_WM_ICON_NAME` falling back to `WM_ICON_NAME`.
+	pub fn icon_name(&self) -> Option<&str> {
+		self.net_icon_name.as_ref().or(self.wm_icon_name.as_ref()).map(|n| n.as_ref())
+	}
+
+	pub fn hints(&self) -> Option<&icccm::WmHints> {
+		self.hints.as_ref()
+	}
+
+	pub fn normal_hints(&self) -> Option<&icccm::SizeHints> {
+		self.normal_hints.as_ref()
+	}
+
+	pub fn transient_for(&self) -> Option<xcb::Window> {
+		self.transient_for
+	}
+
+	pub fn protocols(&self) -> &[xcb::Atom] {
+		&self.protocols
+	}
+
+	pub fn pid(&self) -> Option<u32> {
+		self.pid
+	}
+
+	pub fn desktop(&self) -> Option<u32> {
+		self.desktop
+	}
+
+	pub fn window_type(&self) -> &[xcb::Atom] {
+		&self.window_type
+	}
+
+	pub fn state(&self) -> &[xcb::Atom] {
+		&self.state
+	}
+
+	pub fn strut(&self) -> Option<&ewmh::Extents> {
+		self.strut.as_ref()
+	}
+
+	pub fn strut_partial(&self) -> Option<&ewmh::StrutPartial> {
+		self.strut_partial.as_ref()
+	}
+
+	pub fn frame_extents(&self) -> Option<&ewmh::Extents> {
+		self.frame_extents.as_ref()
+	}
+
+	pub fn icon(&self) -> Option<&ewmh::GetWmIconReply> {
+		self.icon.as_ref()
+	}
+}
@@ -0,0 +1,32 @@
+//! The interface shared by the property cookies of `icccm` and `ewmh`.
+
+use xcb;
+
+/// A pending request, whatever its reply.
+///
+/// Dropping a cookie without taking its reply discards the reply, so
+/// abandoned requests don't pile up in the connection. Cookies of different
+/// kinds can be kept together as `Box<dyn Cookie>`.
+pub trait Cookie {
+	/// The sequence number of the request.
+	fn sequence(&self) -> u32;
+
+	/// Gives up on the reply.
+	fn discard(self) where Self: Sized {
+		drop(self);
+	}
+}
+
+impl<C: Cookie + ?Sized> Cookie for Box<C> {
+	fn sequence(&self) -> u32 {
+		(**self).sequence()
+	}
+}
+
+/// A pending property request.
+pub trait PropertyCookie: Cookie {
+	type Reply;
+
+	/// Waits for the reply.
+	fn get_reply(self) -> Result<Self::Reply, xcb::ReplyError> where Self: Sized;
+}
@@ -0,0 +1,543 @@
+use std::cmp;
+
+use xcb;
+use ewmh;
+
+/// A change to the desktop state of a screen.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DesktopChange {
+	/// `_NET_CURRENT_DESKTOP` changed.
+	DesktopSwitched {
+		from: Option<u32>,
+		to:   Option<u32>,
+	},
+
+	/// `_NET_NUMBER_OF_DESKTOPS` changed.
+	DesktopCount(Option<u32>),
+
+	/// `_NET_DESKTOP_NAMES` changed.
+	DesktopNames,
+
+	/// `_NET_ACTIVE_WINDOW` changed.
+	ActiveWindow {
+		from: Option<xcb::Window>,
+		to:   Option<xcb::Window>,
+	},
+
+	/// A window appeared in `_NET_CLIENT_LIST`.
+	WindowAdded(xcb::Window),
+
+	/// A window disappeared from `_NET_CLIENT_LIST`.
+	WindowRemoved(xcb::Window),
+
+	/// `_NET_CLIENT_LIST_STACKING` changed order.
+	Restacked,
+
+	/// `_NET_WORKAREA` changed.
+	WorkArea,
+
+	/// `_NET_SHOWING_DESKTOP` changed.
+	ShowingDesktop(bool),
+}
+
+/// The desktop related root window properties of a screen, kept up to date
+/// from `PropertyNotify` events on the root window.
+///
+/// The root window must have `PropertyChangeMask` selected for the updates
+/// to be delivered.
+#[derive(Clone, Debug)]
+pub struct DesktopState {
+	screen: ewmh::Screen,
+
+	current:   Option<u32>,
+	count:     Option<u32>,
+	names:     Vec<String>,
+	active:    Option<xcb::Window>,
+	clients:   Vec<xcb::Window>,
+	stacking:  Vec<xcb::Window>,
+	work_area: Vec<ewmh::Geometry>,
+	showing:   bool,
+}
+
+impl DesktopState {
+	/// Fetches the state of the given screen.
+	pub fn fetch(c: &ewmh::Connection, screen: ewmh::Screen) -> DesktopState {
+		let current   = ewmh::get_current_desktop_unchecked(c, screen);
+		let count     = ewmh::get_number_of_desktops_unchecked(c, screen);
+		let names     = ewmh::get_desktop_names_unchecked(c, screen);
+		let active    = ewmh::get_active_window_unchecked(c, screen);
+		let clients   = ewmh::get_client_list_unchecked(c, screen);
+		let stacking  = ewmh::get_client_list_stacking_unchecked(c, screen);
+		let work_area = ewmh::get_work_area_unchecked(c, screen);
+		let showing   = ewmh::get_showing_desktop_unchecked(c, screen);
+
+		DesktopState {
+			screen: screen,
+
+			current:   current.get_reply().ok(),
+			count:     count.get_reply().ok(),
+			names:     names_of(names.get_reply().ok()),
+			active:    window_of(active.get_reply().ok()),
+			clients:   clients.get_reply().map(|r| r.windows().to_vec()).unwrap_or_default(),
+			stacking:  stacking.get_reply().map(|r| r.windows().to_vec()).unwrap_or_default(),
+			work_area: work_area.get_reply().map(|r| r.work_area().to_vec()).unwrap_or_default(),
+			showing:   showing.get_reply().map(|v| v != 0).unwrap_or(false),
+		}
+	}
+
+	/// Refetches the property named by the event if it is one of ours and
+	/// returns what changed.
+	pub fn update(&mut self, c: &ewmh::Connection, event: &xcb::PropertyNotifyEvent) -> Vec<DesktopChange> {
+		let mut changes = Vec::new();
+
+		if event.window() != self.screen.root() {
+			return changes;
+		}
+
+		let atom   = event.atom();
+		let screen = self.screen;
+
+		if atom == c.CURRENT_DESKTOP() {
+			let current = ewmh::get_current_desktop(c, screen).get_reply().ok();
+
+			if current != self.current {
+				changes.push(DesktopChange::DesktopSwitched { from: self.current, to: current });
+				self.current = current;
+			}
+		}
+		else if atom == c.NUMBER_OF_DESKTOPS() {
+			let count = ewmh::get_number_of_desktops(c, screen).get_reply().ok();
+
+			if count != self.count {
+				changes.push(DesktopChange::DesktopCount(count));
+				self.count = count;
+			}
+		}
+		else if atom == c.DESKTOP_NAMES() {
+			let names = names_of(ewmh::get_desktop_names(c, screen).get_reply().ok());
+
+			if names != self.names {
+				changes.push(DesktopChange::DesktopNames);
+				self.names = names;
+			}
+		}
+		else if atom == c.ACTIVE_WINDOW() {
+			let active = window_of(ewmh::get_active_window(c, screen).get_reply().ok());
+
+			if active != self.active {
+				changes.push(DesktopChange::ActiveWindow { from: self.active, to: active });
+				self.active = active;
+			}
+		}
+		else if atom == c.CLIENT_LIST() {
+			let clients = ewmh::get_client_list(c, screen).get_reply()
+				.map(|r| r.windows().to_vec()).unwrap_or_default();
+
+			for &window in &self.clients {
+				if !clients.contains(&window) {
+					changes.push(DesktopChange::WindowRemoved(window));
+				}
+			}
+
+			for &window in &clients {
+				if !self.clients.contains(&window) {
+					changes.push(DesktopChange::WindowAdded(window));
+				}
+			}
+
+			self.clients = clients;
+		}
+		else if atom == c.CLIENT_LIST_STACKING() {
+			let stacking = ewmh::get_client_list_stacking(c, screen).get_reply()
+				.map(|r| r.windows().to_vec()).unwrap_or_default();
+
+			if stacking != self.stacking {
+				changes.push(DesktopChange::Restacked);
+				self.stacking = stacking;
+			}
+		}
+		else if atom == c.WORKAREA() {
+			let work_area = ewmh::get_work_area(c, screen).get_reply()
+				.map(|r| r.work_area().to_vec()).unwrap_or_default();
+
+			if work_area != self.work_area {
+				changes.push(DesktopChange::WorkArea);
+				self.work_area = work_area;
+			}
+		}
+		else if atom == c.SHOWING_DESKTOP() {
+			let showing = ewmh::get_showing_desktop(c, screen).get_reply()
+				.map(|v| v != 0).unwrap_or(false);
+
+			if showing != self.showing {
+				changes.push(DesktopChange::ShowingDesktop(showing));
+				self.showing = showing;
+			}
+		}
+
+		changes
+	}
+
+	pub fn screen(&self) -> ewmh::Screen {
+		self.screen
+	}
+
+	pub fn root(&self) -> xcb::Window {
+		self.screen.root()
+	}
+
+	pub fn current_desktop(&self) -> Option<u32> {
+		self.current
+	}
+
+	pub fn number_of_desktops(&self) -> Option<u32> {
+		self.count
+	}
+
+	pub fn desktop_names(&self) -> &[String] {
+		&self.names
+	}
+
+	/// The name of the given desktop, if any was set.
+	pub fn desktop_name(&self, desktop: u32) -> Option<&str> {
+		self.names.get(desktop as usize).map(|n| n.as_ref())
+	}
+
+	pub fn active_window(&self) -> Option<xcb::Window> {
+		self.active
+	}
+
+	pub fn clients(&self) -> &[xcb::Window] {
+		&self.clients
+	}
+
+	/// The clients in bottom-to-top stacking order.
+	pub fn stacking(&self) -> &[xcb::Window] {
+		&self.stacking
+	}
+
+	/// The work area of every desktop.
+	pub fn work_area(&self) -> &[ewmh::Geometry] {
+		&self.work_area
+	}
+
+	pub fn is_showing_desktop(&self) -> bool {
+		self.showing
+	}
+}
+
+fn names_of(reply: Option<ewmh::GetDesktopNamesReply>) -> Vec<String> {
+	reply.map(|r| r.strings().into_iter().map(|s| s.to_owned()).collect())
+		.unwrap_or_default()
+}
+
+fn window_of(window: Option<xcb::Window>) -> Option<xcb::Window> {
+	window.and_then(|w| if w == xcb::NONE { None } else { Some(w) })
+}
+
+/// Computes the usable area of the screen and its monitors from the struts
+/// reserved by clients such as panels and docks.
+///
+/// Struts are relative to the edges of the root window and only reserve
+/// space on the monitors their start/end range touches.
+#[derive(Clone, Debug)]
+pub struct WorkArea {
+	screen:   ewmh::Geometry,
+	monitors: Vec<ewmh::Geometry>,
+	struts:   Vec<ewmh::StrutPartial>,
+}
+
+impl WorkArea {
+	/// Creates a calculator for a root window of the given size.
+	pub fn new(width: u32, height: u32) -> WorkArea {
+		WorkArea {
+			screen:   ewmh::Geometry { x: 0, y: 0, width: width, height: height },
+			monitors: Vec::new(),
+			struts:   Vec::new(),
+		}
+	}
+
+	/// Adds a monitor rectangle, in root window coordinates.
+	pub fn monitor(mut self, geometry: ewmh::Geometry) -> Self {
+		self.monitors.push(geometry);
+		self
+	}
+
+	/// Adds a `_NET_WM_STRUT`, which covers the whole length of its edge.
+	pub fn strut(mut self, strut: &ewmh::Extents) -> Self {
+		self.struts.push(ewmh::StrutPartial {
+			left:   strut.left,
+			right:  strut.right,
+			top:    strut.top,
+			bottom: strut.bottom,
+
+			left_start_y:   0,
+			left_end_y:     u32::MAX,
+			right_start_y:  0,
+			right_end_y:    u32::MAX,
+			top_start_x:    0,
+			top_end_x:      u32::MAX,
+			bottom_start_x: 0,
+			bottom_end_x:   u32::MAX,
+		});
+
+		self
+	}
+
+	/// Adds a `_NET_WM_STRUT_PARTIAL`.
+	pub fn strut_partial(mut self, strut: &ewmh::StrutPartial) -> Self {
+		self.struts.push(*strut);
+		self
+	}
+
+	/// Fetches and adds the struts of the given clients, `_NET_WM_STRUT_PARTIAL`
+	/// is preferred over `_NET_WM_STRUT` as the specification requires.
+	pub fn clients(mut self, c: &ewmh::Connection, windows: &[xcb::Window]) -> Self {
+		let cookies = windows.iter()
+			.map(|&w| (ewmh::get_wm_strut_partial_unchecked(c, w), ewmh::get_wm_strut_unchecked(c, w)))
+			.collect::<Vec<_>>();
+
+		for (partial, strut) in cookies {
+			if let Ok(partial) = partial.get_reply() {
+				self = self.strut_partial(&partial);
+			}
+			else if let Ok(strut) = strut.get_reply() {
+				self = self.strut(&strut);
+			}
+		}
+
+		self
+	}
+
+	/// The usable area of every monitor in the order they were added, or of
+	/// the whole screen if no monitors were given.
+	pub fn monitors(&self) -> Vec<ewmh::Geometry> {
+		if self.monitors.is_empty() {
+			return vec![self.work_area()];
+		}
+
+		self.monitors.iter().map(|&m| self.apply(m)).collect()
+	}
+
+	/// The single `_NET_WORKAREA` value, the screen minus every strut.
+	pub fn work_area(&self) -> ewmh::Geometry {
+		self.apply(self.screen)
+	}
+
+	/// The `_NET_WORKAREA` value repeated for the given number of desktops,
+	/// ready for `ewmh::set_work_area`.
+	pub fn desktops(&self, count: u32) -> Vec<ewmh::Geometry> {
+		vec![self.work_area(); count as usize]
+	}
+
+	fn apply(&self, area: ewmh::Geometry) -> ewmh::Geometry {
+		let (width, height) = (self.screen.width as i64, self.screen.height as i64);
+
+		let mut left   = area.x as i64;
+		let mut top    = area.y as i64;
+		let mut right  = left + area.width as i64;
+		let mut bottom = top + area.height as i64;
+
+		// Ranges are inclusive.
+		let overlaps = |start: u32, end: u32, from: i64, to: i64|
+			(start as i64) < to && (end as i64) >= from;
+
+		for strut in &self.struts {
+			if strut.left > 0 && (strut.left as i64) > area.x as i64
+				&& overlaps(strut.left_start_y, strut.left_end_y, area.y as i64, area.y as i64 + area.height as i64)
+			{
+				left = cmp::max(left, strut.left as i64);
+			}
+
+			if strut.right > 0 && width - (strut.right as i64) < area.x as i64 + area.width as i64
+				&& overlaps(strut.right_start_y, strut.right_end_y, area.y as i64, area.y as i64 + area.height as i64)
+			{
+				right = cmp::min(right, width - strut.right as i64);
+			}
+
+			if strut.top > 0 && (strut.top as i64) > area.y as i64
+				&& overlaps(strut.top_start_x, strut.top_end_x, area.x as i64, area.x as i64 + area.width as i64)
+			{
+				top = cmp::max(top, strut.top as i64);
+			}
+
+			if strut.bottom > 0 && height - (strut.bottom as i64) < area.y as i64 + area.height as i64
+				&& overlaps(strut.bottom_start_x, strut.bottom_end_x, area.x as i64, area.x as i64 + area.width as i64)
+			{
+				bottom = cmp::min(bottom, height - strut.bottom as i64);
+			}
+		}
+
+		ewmh::Geometry {
+			x:      cmp::max(0, left) as u32,
+			y:      cmp::max(0, top) as u32,
+			width:  cmp::max(0, right - left) as u32,
+			height: cmp::max(0, bottom - top) as u32,
+		}
+	}
+}
+
+/// A direction to move in on the desktop grid.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+	Left,
+	Right,
+	Up,
+	Down,
+}
+
+/// The arrangement of desktops in a grid as described by
+/// `_NET_DESKTOP_LAYOUT`.
+///
+/// Rows and columns are visual, row 0 is at the top and column 0 on the left
+/// whatever the starting corner is.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DesktopGrid {
+	orientation: ewmh::DesktopLayoutOrientation,
+	corner:      ewmh::DesktopLayoutStartingCorner,
+	rows:        u32,
+	columns:     u32,
+	count:       u32,
+}
+
+impl DesktopGrid {
+	/// Creates the grid for the given number of desktops, a zero number of
+	/// rows or columns is computed from the other one.
+	pub fn new(orientation: ewmh::DesktopLayoutOrientation, columns: u32, rows: u32, corner: ewmh::DesktopLayoutStartingCorner, count: u32) -> DesktopGrid {
+		let span = |n: u32| cmp::max(1, count / n + (count % n != 0) as u32);
+
+		let (rows, columns) = match (rows, columns) {
+			(0, 0) => (1, cmp::max(1, count)),
+			(0, c) => (span(c), c),
+			(r, 0) => (r, span(r)),
+
+			// The grid must be able to hold every desktop, grow along the
+			// direction desktops are laid out in.
+			(r, c) if orientation == ewmh::ORIENTATION_VERT => (r, cmp::max(c, span(r))),
+			(r, c) => (cmp::max(r, span(c)), c),
+		};
+
+		DesktopGrid {
+			orientation: orientation,
+			corner:      corner,
+			rows:        rows,
+			columns:     columns,
+			count:       count,
+		}
+	}
+
+	pub fn from_reply(layout: &ewmh::GetDesktopLayoutReply, count: u32) -> DesktopGrid {
+		DesktopGrid::new(layout.orientation(), layout.columns(), layout.rows(), layout.starting_corner(), count)
+	}
+
+	/// Fetches the layout and number of desktops of the screen, a missing
+	/// layout is a single horizontal row.
+	pub fn fetch(c: &ewmh::Connection, screen: ewmh::Screen) -> Result<DesktopGrid, xcb::ReplyError> {
+		let layout = ewmh::get_desktop_layout(c, screen);
+		let count  = ewmh::get_number_of_desktops(c, screen);
+
+		let count = count.get_reply()?;
+
+		Ok(match layout.get_reply() {
+			Ok(layout) =>
+				DesktopGrid::from_reply(&layout, count),
+
+			Err(_) =>
+				DesktopGrid::new(ewmh::ORIENTATION_HORZ, 0, 1, ewmh::TOP_LEFT, count),
+		})
+	}
+
+	pub fn rows(&self) -> u32 {
+		self.rows
+	}
+
+	pub fn columns(&self) -> u32 {
+		self.columns
+	}
+
+	pub fn count(&self) -> u32 {
+		self.count
+	}
+
+	/// The (row, column) of the desktop.
+	pub fn position(&self, desktop: u32) -> Option<(u32, u32)> {
+		if desktop >= self.count {
+			return None;
+		}
+
+		let (row, column) = if self.orientation == ewmh::ORIENTATION_VERT {
+			(desktop % self.rows, desktop / self.rows)
+		}
+		else {
+			(desktop / self.columns, desktop % self.columns)
+		};
+
+		Some(self.flip(row, column))
+	}
+
+	/// The desktop at the given (row, column), `None` if outside the grid or
+	/// on an empty cell of a partial grid.
+	pub fn desktop(&self, row: u32, column: u32) -> Option<u32> {
+		if row >= self.rows || column >= self.columns {
+			return None;
+		}
+
+		let (row, column) = self.flip(row, column);
+		let desktop = if self.orientation == ewmh::ORIENTATION_VERT {
+			column * self.rows + row
+		}
+		else {
+			row * self.columns + column
+		};
+
+		if desktop < self.count { Some(desktop) } else { None }
+	}
+
+	/// The desktop next to the given one, empty cells are skipped and with
+	/// `wrap` moving past an edge continues from the opposite one.
+	pub fn neighbor(&self, desktop: u32, direction: Direction, wrap: bool) -> Option<u32> {
+		let (mut row, mut column) = self.position(desktop)?;
+
+		loop {
+			let (next_row, next_column) = match direction {
+				Direction::Left  => (Some(row), column.checked_sub(1)),
+				Direction::Right => (Some(row), Some(column + 1).filter(|&c| c < self.columns)),
+				Direction::Up    => (row.checked_sub(1), Some(column)),
+				Direction::Down  => (Some(row + 1).filter(|&r| r < self.rows), Some(column)),
+			};
+
+			row = match next_row {
+				Some(row) => row,
+				None if wrap => if direction == Direction::Up { self.rows - 1 } else { 0 },
+				None => return None,
+			};
+
+			column = match next_column {
+				Some(column) => column,
+				None if wrap => if direction == Direction::Left { self.columns - 1 } else { 0 },
+				None => return None,
+			};
+
+			match self.desktop(row, column) {
+				Some(next) if next == desktop =>
+					return None,
+
+				Some(next) =>
+					return Some(next),
+
+				None =>
+					continue,
+			}
+		}
+	}
+
+	// Flipping is its own inverse so it converts both ways.
+	fn flip(&self, row: u32, column: u32) -> (u32, u32) {
+		match self.corner {
+			ewmh::TOP_RIGHT    => (row, self.columns - 1 - column),
+			ewmh::BOTTOM_LEFT  => (self.rows - 1 - row, column),
+			ewmh::BOTTOM_RIGHT => (self.rows - 1 - row, self.columns - 1 - column),
+			_                  => (row, column),
+		}
+	}
+}
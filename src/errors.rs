@@ -0,0 +1,215 @@
+use std::fmt;
+use std::ptr;
+use std::ffi::CStr;
+use std::marker::PhantomData;
+
+use xcb;
+use ffi::errors::*;
+use libc::c_char;
+
+/// Looks up the names of requests, errors and events, including the ones
+/// of the extensions known to the server.
+pub struct Errors<'a> {
+	ptr:  *mut xcb_errors_context_t,
+	conn: PhantomData<&'a xcb::Connection>,
+}
+
+/// A name along with the extension defining it, if any.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Name<'a> {
+	pub name:      &'a str,
+	pub extension: Option<&'a str>,
+}
+
+impl<'a> fmt::Display for Name<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if let Some(extension) = self.extension {
+			write!(f, "{}::", extension)?;
+		}
+
+		f.write_str(self.name)
+	}
+}
+
+impl<'a> Errors<'a> {
+	/// Creates the lookup context, `None` if the connection failed.
+	pub fn new(c: &'a xcb::Connection) -> Option<Errors<'a>> {
+		unsafe {
+			let mut ptr = ptr::null_mut();
+
+			if xcb_errors_context_new(c.get_raw_conn(), &mut ptr) != 0 {
+				return None;
+			}
+
+			Some(Errors {
+				ptr:  ptr,
+				conn: PhantomData,
+			})
+		}
+	}
+
+	pub fn major_name(&self, major: u8) -> &str {
+		unsafe {
+			string(xcb_errors_get_name_for_major_code(self.ptr, major)).unwrap_or("")
+		}
+	}
+
+	/// The name of an extension request, `None` for core requests.
+	pub fn minor_name(&self, major: u8, minor: u16) -> Option<&str> {
+		unsafe {
+			string(xcb_errors_get_name_for_minor_code(self.ptr, major, minor))
+		}
+	}
+
+	pub fn error_name(&self, code: u8) -> Name<'_> {
+		unsafe {
+			let mut extension = ptr::null();
+			let     name      = xcb_errors_get_name_for_error(self.ptr, code, &mut extension);
+
+			Name {
+				name:      string(name).unwrap_or(""),
+				extension: string(extension),
+			}
+		}
+	}
+
+	pub fn event_name(&self, event: &xcb::GenericEvent) -> Name<'_> {
+		unsafe {
+			let mut extension = ptr::null();
+			let     name      = xcb_errors_get_name_for_xcb_event(self.ptr, event.ptr, &mut extension);
+
+			Name {
+				name:      string(name).unwrap_or(""),
+				extension: string(extension),
+			}
+		}
+	}
+
+	/// The name of an event by its code, without the sent event bit.
+	pub fn core_event_name(&self, code: u8) -> Name<'_> {
+		unsafe {
+			let mut extension = ptr::null();
+			let     name      = xcb_errors_get_name_for_core_event(self.ptr, code, &mut extension);
+
+			Name {
+				name:      string(name).unwrap_or(""),
+				extension: string(extension),
+			}
+		}
+	}
+
+	/// The name of a generic event of the extension, `None` if unknown.
+	pub fn xge_event_name(&self, major: u8, event_type: u16) -> Option<&str> {
+		unsafe {
+			string(xcb_errors_get_name_for_xge_event(self.ptr, major, event_type))
+		}
+	}
+
+	/// Describes the error, as in `BadWindow in ChangeProperty (sequence 1234)`.
+	///
+	/// Returns `None` for errors without a server error behind them.
+	pub fn describe(&self, error: &xcb::GenericError) -> Option<Description<'_>> {
+		if error.ptr.is_null() {
+			return None;
+		}
+
+		unsafe {
+			let error = &*error.ptr;
+
+			Some(Description {
+				errors:   self,
+				code:     error.error_code,
+				major:    error.major_code,
+				minor:    error.minor_code,
+				sequence: error.full_sequence,
+			})
+		}
+	}
+
+	/// Describes the error of a failed cookie, including the ones without a
+	/// server error behind them.
+	pub fn describe_reply(&self, error: &xcb::ReplyError) -> ReplyDescription<'_> {
+		match *error {
+			xcb::ReplyError::GenericError(ref error) =>
+				self.describe(error).map(ReplyDescription::Error).unwrap_or(ReplyDescription::Missing),
+
+			xcb::ReplyError::NullResponse =>
+				ReplyDescription::Missing,
+		}
+	}
+}
+
+impl<'a> Drop for Errors<'a> {
+	fn drop(&mut self) {
+		unsafe {
+			xcb_errors_context_free(self.ptr);
+		}
+	}
+}
+
+/// The named form of an error, see `Errors::describe`.
+pub struct Description<'a> {
+	errors:   &'a Errors<'a>,
+	code:     u8,
+	major:    u8,
+	minor:    u16,
+	sequence: u32,
+}
+
+impl<'a> Description<'a> {
+	pub fn error(&self) -> Name<'a> {
+		self.errors.error_name(self.code)
+	}
+
+	/// The name of the failed request, prefixed by its extension if any.
+	pub fn request(&self) -> Name<'a> {
+		let major = self.errors.major_name(self.major);
+
+		match self.errors.minor_name(self.major, self.minor) {
+			Some(minor) =>
+				Name { name: minor, extension: Some(major) },
+
+			None =>
+				Name { name: major, extension: None },
+		}
+	}
+
+	pub fn sequence(&self) -> u32 {
+		self.sequence
+	}
+}
+
+impl<'a> fmt::Display for Description<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} in {} (sequence {})", self.error(), self.request(), self.sequence)
+	}
+}
+
+/// The named form of a `xcb::ReplyError`, see `Errors::describe_reply`.
+pub enum ReplyDescription<'a> {
+	Error(Description<'a>),
+
+	/// There was no reply, nor any error from the server.
+	Missing,
+}
+
+impl<'a> fmt::Display for ReplyDescription<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ReplyDescription::Error(ref description) =>
+				description.fmt(f),
+
+			ReplyDescription::Missing =>
+				f.write_str("missing reply"),
+		}
+	}
+}
+
+unsafe fn string<'a>(ptr: *const c_char) -> Option<&'a str> {
+	if ptr.is_null() {
+		None
+	}
+	else {
+		CStr::from_ptr(ptr).to_str().ok()
+	}
+}
@@ -1,14 +1,21 @@
+use std::cmp;
+use std::fmt;
+use std::error;
 use std::mem;
 use std::ptr;
 use std::slice;
 use std::str;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
 
 use xcb;
 use xcb::ffi::*;
 use ffi::ewmh::*;
-use libc::c_int;
-use util::utf8;
+use libc::{self, c_int};
+use util::{utf8, event};
 
 pub type Coordinates = xcb_ewmh_coordinates_t;
 
@@ -114,13 +121,26 @@ impl Extents {
 	}
 }
 
-pub struct WmIcon {
+/// An icon from `_NET_WM_ICON`, pixels are ARGB32 in row-major order with
+/// straight (not premultiplied) alpha.
+#[derive(Clone, Copy)]
+pub struct WmIcon<'a> {
 	width:  u32,
 	height: u32,
-	id:     u32,
+	data:   &'a [u32],
 }
 
-impl WmIcon {
+/// The filter used when resizing icons.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IconFilter {
+	/// Pick the closest source pixel, cheap and crisp for pixel art.
+	Nearest,
+
+	/// Average the covered source pixels, better looking when shrinking.
+	Box,
+}
+
+impl<'a> WmIcon<'a> {
 	pub fn width(&self) -> u32 {
 		self.width
 	}
@@ -129,33 +149,133 @@ impl WmIcon {
 		self.height
 	}
 
+	/// The first pixel of the icon.
 	pub fn id(&self) -> u32 {
-		self.id
+		self.data.first().cloned().unwrap_or(0)
+	}
+
+	/// The ARGB32 pixels.
+	pub fn pixels(&self) -> &'a [u32] {
+		self.data
+	}
+
+	/// Converts the pixels to RGBA bytes with straight alpha.
+	pub fn to_rgba(&self) -> Vec<u8> {
+		let mut result = Vec::with_capacity(self.data.len() * 4);
+
+		for &pixel in self.data {
+			let (a, r, g, b) = argb(pixel);
+			result.extend_from_slice(&[r, g, b, a]);
+		}
+
+		result
+	}
+
+	/// Converts the pixels to RGBA bytes with premultiplied alpha.
+	pub fn to_rgba_premultiplied(&self) -> Vec<u8> {
+		let mut result = Vec::with_capacity(self.data.len() * 4);
+
+		for &pixel in self.data {
+			let (a, r, g, b) = argb(pixel);
+			result.extend_from_slice(&[premultiply(r, a), premultiply(g, a), premultiply(b, a), a]);
+		}
+
+		result
+	}
+
+	/// Resizes the icon to the given size, returning ARGB32 pixels.
+	pub fn resize(&self, width: u32, height: u32, filter: IconFilter) -> Vec<u32> {
+		let (sw, sh) = (self.width as usize, self.height as usize);
+		let (dw, dh) = (width as usize, height as usize);
+
+		if sw == 0 || sh == 0 || dw == 0 || dh == 0 {
+			return vec![0; dw * dh];
+		}
+
+		if sw == dw && sh == dh {
+			return self.data.to_vec();
+		}
+
+		let mut result = Vec::with_capacity(dw * dh);
+
+		for dy in 0 .. dh {
+			for dx in 0 .. dw {
+				result.push(match filter {
+					IconFilter::Nearest =>
+						self.data[(dy * sh / dh) * sw + dx * sw / dw],
+
+					IconFilter::Box => {
+						let (x0, y0) = (dx * sw / dw, dy * sh / dh);
+						let x1 = cmp::max(x0 + 1, (dx + 1) * sw / dw);
+						let y1 = cmp::max(y0 + 1, (dy + 1) * sh / dh);
+
+						// Colors are weighted by alpha so transparent pixels
+						// don't bleed into the result.
+						let (mut sa, mut sr, mut sg, mut sb, mut n) = (0u64, 0u64, 0u64, 0u64, 0u64);
+
+						for y in y0 .. y1 {
+							for &pixel in &self.data[y * sw + x0 .. y * sw + x1] {
+								let (a, r, g, b) = argb(pixel);
+								let a = a as u64;
+
+								sa += a;
+								sr += r as u64 * a;
+								sg += g as u64 * a;
+								sb += b as u64 * a;
+								n  += 1;
+							}
+						}
+
+						if sa == 0 {
+							0
+						}
+						else {
+							(((sa + n / 2) / n) as u32) << 24 |
+							(((sr + sa / 2) / sa) as u32) << 16 |
+							(((sg + sa / 2) / sa) as u32) << 8 |
+							((sb + sa / 2) / sa) as u32
+						}
+					}
+				});
+			}
+		}
+
+		result
 	}
 }
 
+fn argb(pixel: u32) -> (u8, u8, u8, u8) {
+	((pixel >> 24) as u8, (pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8)
+}
+
+fn premultiply(value: u8, alpha: u8) -> u8 {
+	((value as u32 * alpha as u32 + 127) / 255) as u8
+}
 
-pub type WmIconIterator = xcb_ewmh_wm_icon_iterator_t;
+pub struct WmIconIterator<'a> {
+	iter:   xcb_ewmh_wm_icon_iterator_t,
+	marker: PhantomData<&'a GetWmIconReply>,
+}
 
-impl Iterator for WmIconIterator {
-	type Item = WmIcon;
+impl<'a> Iterator for WmIconIterator<'a> {
+	type Item = WmIcon<'a>;
 
 	fn next(&mut self) -> Option<Self::Item> {
 		unsafe {
-			if self.rem == 0 {
+			if self.iter.rem == 0 {
 				None
 			}
 			else {
-				let width  = self.width;
-				let height = self.height;
-				let id     = *self.data;
+				let width  = self.iter.width;
+				let height = self.iter.height;
+				let data   = slice::from_raw_parts(self.iter.data, width as usize * height as usize);
 
-				xcb_ewmh_get_wm_icon_next(self);
+				xcb_ewmh_get_wm_icon_next(&mut self.iter);
 
 				Some(WmIcon {
 					width:  width,
 					height: height,
-					id:     id
+					data:   data,
 				})
 			}
 		}
@@ -222,9 +342,463 @@ pub const STATE_REMOVE: StateAction = 0;
 pub const STATE_ADD:    StateAction = 1;
 pub const STATE_TOGGLE: StateAction = 2;
 
+/// A request sent by a client to the root window, as received by the window
+/// manager.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClientRequest {
+	WmState {
+		window: xcb::Window,
+		action: StateAction,
+		first:  xcb::Atom,
+		second: xcb::Atom,
+		source: ClientSourceType,
+	},
+
+	ActiveWindow {
+		window:    xcb::Window,
+		source:    ClientSourceType,
+		timestamp: xcb::Timestamp,
+		current:   xcb::Window,
+	},
+
+	CloseWindow {
+		window:    xcb::Window,
+		timestamp: xcb::Timestamp,
+		source:    ClientSourceType,
+	},
+
+	MoveResizeWindow {
+		window:  xcb::Window,
+		gravity: xcb::Gravity,
+		flags:   MoveResizeWindowFlags,
+		source:  ClientSourceType,
+		x:       u32,
+		y:       u32,
+		width:   u32,
+		height:  u32,
+	},
+
+	WmMoveResize {
+		window:    xcb::Window,
+		x_root:    u32,
+		y_root:    u32,
+		direction: MoveResizeDirection,
+		button:    xcb::ButtonIndex,
+		source:    ClientSourceType,
+	},
+
+	CurrentDesktop {
+		desktop:   u32,
+		timestamp: xcb::Timestamp,
+	},
+
+	WmDesktop {
+		window:  xcb::Window,
+		desktop: u32,
+		source:  ClientSourceType,
+	},
+
+	RestackWindow {
+		window:  xcb::Window,
+		sibling: xcb::Window,
+		detail:  xcb::StackMode,
+		source:  ClientSourceType,
+	},
+
+	RequestFrameExtents {
+		window: xcb::Window,
+	},
+
+	/// `_GTK_SHOW_WINDOW_MENU`, the client wants the window menu shown at
+	/// the given position.
+	ShowWindowMenu {
+		window: xcb::Window,
+		device: u32,
+		x_root: i32,
+		y_root: i32,
+	},
+}
+
+// Defines the names of the atoms stored in `xcb_ewmh_connection_t` along with
+// their assignment by field, so nothing depends on the order libxcb-ewmh
+// interns them in.
+macro_rules! atoms {
+	($($name:ident,)*) => (
+		const ATOMS: &[&str] = &[$(stringify!($name)),*];
+
+		fn set_atoms(ewmh: &mut xcb_ewmh_connection_t, atoms: &[xcb::Atom]) {
+			let mut atoms = atoms.iter().cloned();
+			$(ewmh.$name = atoms.next().unwrap_or(xcb::ATOM_NONE);)*
+		}
+	);
+}
+
+atoms! {
+	_NET_SUPPORTED,
+	_NET_CLIENT_LIST,
+	_NET_CLIENT_LIST_STACKING,
+	_NET_NUMBER_OF_DESKTOPS,
+	_NET_DESKTOP_GEOMETRY,
+	_NET_DESKTOP_VIEWPORT,
+	_NET_CURRENT_DESKTOP,
+	_NET_DESKTOP_NAMES,
+	_NET_ACTIVE_WINDOW,
+	_NET_WORKAREA,
+	_NET_SUPPORTING_WM_CHECK,
+	_NET_VIRTUAL_ROOTS,
+	_NET_DESKTOP_LAYOUT,
+	_NET_SHOWING_DESKTOP,
+	_NET_CLOSE_WINDOW,
+	_NET_MOVERESIZE_WINDOW,
+	_NET_WM_MOVERESIZE,
+	_NET_RESTACK_WINDOW,
+	_NET_REQUEST_FRAME_EXTENTS,
+	_NET_WM_NAME,
+	_NET_WM_VISIBLE_NAME,
+	_NET_WM_ICON_NAME,
+	_NET_WM_VISIBLE_ICON_NAME,
+	_NET_WM_DESKTOP,
+	_NET_WM_WINDOW_TYPE,
+	_NET_WM_STATE,
+	_NET_WM_ALLOWED_ACTIONS,
+	_NET_WM_STRUT,
+	_NET_WM_STRUT_PARTIAL,
+	_NET_WM_ICON_GEOMETRY,
+	_NET_WM_ICON,
+	_NET_WM_PID,
+	_NET_WM_HANDLED_ICONS,
+	_NET_WM_USER_TIME,
+	_NET_WM_USER_TIME_WINDOW,
+	_NET_FRAME_EXTENTS,
+	_NET_WM_PING,
+	_NET_WM_SYNC_REQUEST,
+	_NET_WM_SYNC_REQUEST_COUNTER,
+	_NET_WM_FULLSCREEN_MONITORS,
+	_NET_WM_FULL_PLACEMENT,
+	UTF8_STRING,
+	WM_PROTOCOLS,
+	MANAGER,
+	_NET_WM_WINDOW_TYPE_DESKTOP,
+	_NET_WM_WINDOW_TYPE_DOCK,
+	_NET_WM_WINDOW_TYPE_TOOLBAR,
+	_NET_WM_WINDOW_TYPE_MENU,
+	_NET_WM_WINDOW_TYPE_UTILITY,
+	_NET_WM_WINDOW_TYPE_SPLASH,
+	_NET_WM_WINDOW_TYPE_DIALOG,
+	_NET_WM_WINDOW_TYPE_DROPDOWN_MENU,
+	_NET_WM_WINDOW_TYPE_POPUP_MENU,
+	_NET_WM_WINDOW_TYPE_TOOLTIP,
+	_NET_WM_WINDOW_TYPE_NOTIFICATION,
+	_NET_WM_WINDOW_TYPE_COMBO,
+	_NET_WM_WINDOW_TYPE_DND,
+	_NET_WM_WINDOW_TYPE_NORMAL,
+	_NET_WM_STATE_MODAL,
+	_NET_WM_STATE_STICKY,
+	_NET_WM_STATE_MAXIMIZED_VERT,
+	_NET_WM_STATE_MAXIMIZED_HORZ,
+	_NET_WM_STATE_SHADED,
+	_NET_WM_STATE_SKIP_TASKBAR,
+	_NET_WM_STATE_SKIP_PAGER,
+	_NET_WM_STATE_HIDDEN,
+	_NET_WM_STATE_FULLSCREEN,
+	_NET_WM_STATE_ABOVE,
+	_NET_WM_STATE_BELOW,
+	_NET_WM_STATE_DEMANDS_ATTENTION,
+	_NET_WM_ACTION_MOVE,
+	_NET_WM_ACTION_RESIZE,
+	_NET_WM_ACTION_MINIMIZE,
+	_NET_WM_ACTION_SHADE,
+	_NET_WM_ACTION_STICK,
+	_NET_WM_ACTION_MAXIMIZE_HORZ,
+	_NET_WM_ACTION_MAXIMIZE_VERT,
+	_NET_WM_ACTION_FULLSCREEN,
+	_NET_WM_ACTION_CHANGE_DESKTOP,
+	_NET_WM_ACTION_CLOSE,
+	_NET_WM_ACTION_ABOVE,
+	_NET_WM_ACTION_BELOW,
+}
+
+// Defines the atoms of common extensions to the specification that
+// `xcb_ewmh_connection_t` doesn't cover, and our own, interned along with it
+// and assigned to the fields of `Extensions` by name.
+macro_rules! extensions {
+	($($field:ident => $name:expr,)*) => (
+		const EXTENSIONS: &[&str] = &[$($name),*];
+
+		struct Extensions {
+			$($field: xcb::Atom,)*
+		}
+
+		impl Extensions {
+			fn new(atoms: &[xcb::Atom]) -> Extensions {
+				let mut atoms = atoms.iter().cloned();
+
+				Extensions {
+					$($field: atoms.next().unwrap_or(xcb::ATOM_NONE),)*
+				}
+			}
+		}
+	);
+}
+
+extensions! {
+	wm_window_opacity    => "_NET_WM_WINDOW_OPACITY",
+	wm_opaque_region     => "_NET_WM_OPAQUE_REGION",
+	wm_bypass_compositor => "_NET_WM_BYPASS_COMPOSITOR",
+	wm_state_focused     => "_NET_WM_STATE_FOCUSED",
+	gtk_frame_extents    => "_GTK_FRAME_EXTENTS",
+	gtk_show_window_menu => "_GTK_SHOW_WINDOW_MENU",
+	timestamp            => "_XCB_UTIL_TIMESTAMP",
+	motif_wm_hints       => "_MOTIF_WM_HINTS",
+}
+
+/// An atom that could not be interned while setting up a `Connection`.
+#[derive(Debug)]
+pub struct AtomError {
+	name:  String,
+	error: xcb::ReplyError,
+}
+
+impl AtomError {
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn error(&self) -> &xcb::ReplyError {
+		&self.error
+	}
+
+	pub fn into_error(self) -> xcb::ReplyError {
+		self.error
+	}
+}
+
+impl fmt::Display for AtomError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "failed to intern {}: {}", self.name, self.error)
+	}
+}
+
+impl error::Error for AtomError { }
+
+impl From<AtomError> for xcb::ReplyError {
+	fn from(value: AtomError) -> Self {
+		value.error
+	}
+}
+
+// The in-flight InternAtom requests for a connection, `ATOMS` followed by
+// `_NET_WM_CM_Sn` for every screen and then `EXTENSIONS`. Whatever isn't
+// collected is discarded.
+struct Pending {
+	ewmh:    xcb_ewmh_connection_t,
+	cookies: Vec<xcb_intern_atom_cookie_t>,
+}
+
+impl Pending {
+	// Sets up the screens the way `xcb_ewmh_init_atoms` does, the arrays are
+	// freed by `xcb_ewmh_connection_wipe`.
+	unsafe fn start(xcb: &xcb::Connection) -> Pending {
+		let c       = xcb.get_raw_conn();
+		let roots   = xcb.get_setup().roots().map(|screen| screen.ptr).collect::<Vec<_>>();
+		let screens = roots.len();
+
+		let mut ewmh: xcb_ewmh_connection_t = mem::zeroed();
+		ewmh.connection    = c;
+		ewmh.nb_screens    = screens as c_int;
+		ewmh.screens       = libc::malloc(screens * mem::size_of::<*mut xcb_screen_t>()) as *mut _;
+		ewmh._NET_WM_CM_Sn = libc::calloc(screens, mem::size_of::<xcb::Atom>()) as *mut _;
+		ptr::copy_nonoverlapping(roots.as_ptr(), ewmh.screens, screens);
+
+		let intern = |name: &str| xcb_intern_atom(c, 0, name.len() as u16, name.as_ptr() as *const _);
+		let cookies = ATOMS.iter().map(|name| intern(name))
+			.chain((0 .. screens).map(|screen| intern(&format!("_NET_WM_CM_S{}", screen))))
+			.chain(EXTENSIONS.iter().map(|name| intern(name)))
+			.collect();
+
+		Pending {
+			ewmh:    ewmh,
+			cookies: cookies,
+		}
+	}
+
+	fn name(&self, index: usize) -> String {
+		let screens = self.ewmh.nb_screens as usize;
+
+		if index < ATOMS.len() {
+			ATOMS[index].to_owned()
+		}
+		else if index < ATOMS.len() + screens {
+			format!("_NET_WM_CM_S{}", index - ATOMS.len())
+		}
+		else {
+			EXTENSIONS[index - ATOMS.len() - screens].to_owned()
+		}
+	}
+
+	// Collects every reply, the first failure is reported once all of them
+	// have been consumed.
+	unsafe fn finish(mut self) -> Result<(xcb_ewmh_connection_t, Extensions), AtomError> {
+		let c       = self.ewmh.connection;
+		let screens = self.ewmh.nb_screens as usize;
+		let cookies = mem::take(&mut self.cookies);
+
+		let mut atoms  = Vec::with_capacity(cookies.len());
+		let mut failed = None;
+
+		for (index, cookie) in cookies.into_iter().enumerate() {
+			match intern_reply(c, cookie) {
+				Ok(atom) =>
+					atoms.push(atom),
+
+				Err(error) => {
+					failed = failed.or_else(|| Some(AtomError {
+						name:  self.name(index),
+						error: error,
+					}));
+
+					atoms.push(xcb::ATOM_NONE);
+				}
+			}
+		}
+
+		if let Some(err) = failed {
+			return Err(err);
+		}
+
+		let (ewmh, rest) = atoms.split_at(ATOMS.len());
+		let (cm, ext)    = rest.split_at(screens);
+
+		set_atoms(&mut self.ewmh, ewmh);
+		ptr::copy_nonoverlapping(cm.as_ptr(), self.ewmh._NET_WM_CM_Sn, screens);
+
+		let ewmh = ptr::read(&self.ewmh);
+		mem::forget(self);
+
+		Ok((ewmh, Extensions::new(ext)))
+	}
+}
+
+impl Drop for Pending {
+	fn drop(&mut self) {
+		unsafe {
+			for cookie in &self.cookies {
+				xcb_discard_reply(self.ewmh.connection, xcb::CookieSeq::sequence(cookie));
+			}
+
+			xcb_ewmh_connection_wipe(&mut self.ewmh);
+		}
+	}
+}
+
+unsafe fn intern_reply(c: *mut xcb_connection_t, cookie: xcb_intern_atom_cookie_t) -> Result<xcb::Atom, xcb::ReplyError> {
+	let mut err: *mut xcb_generic_error_t = ptr::null_mut();
+	let reply = xcb_intern_atom_reply(c, cookie, &mut err);
+
+	if reply.is_null() {
+		return Err(if err.is_null() {
+			xcb::ReplyError::NullResponse
+		}
+		else {
+			xcb::ReplyError::GenericError(xcb::GenericError { ptr: err })
+		});
+	}
+
+	let atom = (*reply).atom;
+	libc::free(reply as *mut _);
+
+	Ok(atom)
+}
+
+/// A connection whose EWMH atoms are still being interned, see
+/// `Connection::start`.
+pub struct PendingConnection {
+	pending: Pending,
+	xcb:     xcb::Connection,
+}
+
+#[cfg(feature = "thread")]
+unsafe impl Send for PendingConnection { }
+
+impl PendingConnection {
+	/// Waits for the atoms, giving back the connection if any failed.
+	pub fn finish(self) -> Result<Connection, (AtomError, xcb::Connection)> {
+		match unsafe { self.pending.finish() } {
+			Ok((ewmh, ext)) =>
+				Ok(Connection::new(self.xcb, ewmh, ext)),
+
+			Err(err) =>
+				Err((err, self.xcb))
+		}
+	}
+}
+
+/// A screen of the display, its index is known to be valid for the
+/// connection it came from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Screen {
+	index: i32,
+	root:  xcb::Window,
+}
+
+impl Screen {
+	pub fn index(&self) -> i32 {
+		self.index
+	}
+
+	pub fn root(&self) -> xcb::Window {
+		self.root
+	}
+}
+
+/// Why getting a server timestamp failed.
+#[derive(Debug)]
+pub enum TimestampError {
+	/// The `PropertyNotify` didn't arrive in time, the window may be gone or
+	/// not have `EVENT_MASK_PROPERTY_CHANGE` selected.
+	Timeout,
+
+	Reply(xcb::ReplyError),
+}
+
+impl fmt::Display for TimestampError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			TimestampError::Timeout =>
+				f.write_str("timed out waiting for the server timestamp"),
+
+			TimestampError::Reply(ref err) =>
+				err.fmt(f),
+		}
+	}
+}
+
+impl error::Error for TimestampError { }
+
+impl From<xcb::ReplyError> for TimestampError {
+	fn from(value: xcb::ReplyError) -> Self {
+		TimestampError::Reply(value)
+	}
+}
+
+/// A screen index out of range for the connection.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct InvalidScreen(pub i32);
+
+impl fmt::Display for InvalidScreen {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "invalid screen index {}", self.0)
+	}
+}
+
+impl error::Error for InvalidScreen { }
+
 pub struct Connection {
-	xcb:  xcb::Connection,
-	ewmh: xcb_ewmh_connection_t,
+	xcb:       xcb::Connection,
+	ewmh:      xcb_ewmh_connection_t,
+	ext:       Extensions,
+	timestamp: AtomicU32,
+	default:   AtomicBool,
 }
 
 #[cfg(feature = "thread")]
@@ -233,23 +807,70 @@ unsafe impl<'a> Send for Connection { }
 unsafe impl<'a> Sync for Connection { }
 
 impl Connection {
-	pub fn connect(xcb: xcb::Connection) -> Result<Connection, (xcb::ReplyError, xcb::Connection)> {
-		unsafe {
-			let mut ewmh = mem::zeroed();
-			let mut err: *mut xcb_generic_error_t = ptr::null_mut();
+	pub fn connect(xcb: xcb::Connection) -> Result<Connection, (AtomError, xcb::Connection)> {
+		Connection::start(xcb).finish()
+	}
 
-			let cookie = xcb_ewmh_init_atoms(xcb.get_raw_conn(), &mut ewmh);
-			xcb_ewmh_init_atoms_replies(&mut ewmh, cookie, &mut err);
+	/// Sends the requests for the atoms without waiting for them, so other
+	/// setup can happen in the meantime.
+	pub fn start(xcb: xcb::Connection) -> PendingConnection {
+		PendingConnection {
+			pending: unsafe { Pending::start(&xcb) },
+			xcb:     xcb,
+		}
+	}
 
-			if err.is_null() {
-				Ok(Connection {
+	/// Interns the atoms over a connection shared with other users.
+	pub fn connect_shared(xcb: Arc<xcb::Connection>) -> Result<SharedConnection, (AtomError, Arc<xcb::Connection>)> {
+		match unsafe { Pending::start(&xcb).finish() } {
+			Ok((ewmh, ext)) =>
+				Ok(SharedConnection {
+					conn: mem::ManuallyDrop::new(Connection::new(unsafe { Connection::alias(&xcb) }, ewmh, ext)),
 					xcb:  xcb,
-					ewmh: ewmh,
-				})
-			}
-			else {
-				Err((xcb::ReplyError::GenericError(xcb::GenericError { ptr: err }), xcb))
-			}
+				}),
+
+			Err(err) =>
+				Err((err, xcb))
+		}
+	}
+
+	/// Interns the atoms over a connection owned by someone else.
+	pub fn connect_borrowed(xcb: &xcb::Connection) -> Result<BorrowedConnection, AtomError> {
+		unsafe {
+			let (ewmh, ext) = Pending::start(xcb).finish()?;
+
+			Ok(BorrowedConnection {
+				conn:   mem::ManuallyDrop::new(Connection::new(Connection::alias(xcb), ewmh, ext)),
+				marker: PhantomData,
+			})
+		}
+	}
+
+	fn new(xcb: xcb::Connection, ewmh: xcb_ewmh_connection_t, ext: Extensions) -> Connection {
+		Connection {
+			xcb:       xcb,
+			ewmh:      ewmh,
+			ext:       ext,
+			timestamp: AtomicU32::new(xcb::CURRENT_TIME),
+			default:   AtomicBool::new(false),
+		}
+	}
+
+	// An `xcb::Connection` over the same raw connection, the `Connection` made
+	// from it must never be dropped.
+	unsafe fn alias(xcb: &xcb::Connection) -> xcb::Connection {
+		xcb::Connection::from_raw_conn(xcb.get_raw_conn())
+	}
+
+	/// Wipes the atoms and gives back the connection.
+	pub fn into_inner(mut self) -> xcb::Connection {
+		unsafe {
+			xcb_ewmh_connection_wipe(&mut self.ewmh);
+
+			let xcb = ptr::read(&self.xcb);
+			mem::forget(self);
+
+			xcb
 		}
 	}
 
@@ -258,6 +879,314 @@ impl Connection {
 		&self.ewmh as *const _ as *mut _
 	}
 
+	/// The screens of the display.
+	pub fn screens(&self) -> Vec<Screen> {
+		(0 .. self.ewmh.nb_screens).map(|index| Screen {
+			index: index,
+			root:  unsafe { (**self.ewmh.screens.offset(index as isize)).root },
+		}).collect()
+	}
+
+	pub fn screen(&self, index: i32) -> Result<Screen, InvalidScreen> {
+		if index < 0 || index >= self.ewmh.nb_screens {
+			return Err(InvalidScreen(index));
+		}
+
+		Ok(Screen {
+			index: index,
+			root:  unsafe { (**self.ewmh.screens.offset(index as isize)).root },
+		})
+	}
+
+	pub fn root(&self, index: i32) -> Result<xcb::Window, InvalidScreen> {
+		self.screen(index).map(|screen| screen.root)
+	}
+
+	/// The last server timestamp seen through `observe`, `CURRENT_TIME` if
+	/// none was yet.
+	pub fn timestamp(&self) -> xcb::Timestamp {
+		self.timestamp.load(Ordering::Relaxed)
+	}
+
+	/// Makes the client messages taking a timestamp send the last one seen
+	/// when given `CURRENT_TIME`, off by default.
+	///
+	/// Only useful if every event is fed to `observe`. Selection ownership
+	/// always uses the given timestamp.
+	pub fn set_default_timestamp(&self, enabled: bool) {
+		self.default.store(enabled, Ordering::Relaxed);
+	}
+
+	/// Remembers the timestamp carried by the event, if it has one and it's
+	/// newer than the last one seen.
+	pub fn observe(&self, event: &xcb::GenericEvent) {
+		let time = unsafe {
+			match event.response_type() & !0x80 {
+				xcb::KEY_PRESS | xcb::KEY_RELEASE =>
+					xcb::cast_event::<xcb::KeyPressEvent>(event).time(),
+
+				xcb::BUTTON_PRESS | xcb::BUTTON_RELEASE =>
+					xcb::cast_event::<xcb::ButtonPressEvent>(event).time(),
+
+				xcb::MOTION_NOTIFY =>
+					xcb::cast_event::<xcb::MotionNotifyEvent>(event).time(),
+
+				xcb::ENTER_NOTIFY | xcb::LEAVE_NOTIFY =>
+					xcb::cast_event::<xcb::EnterNotifyEvent>(event).time(),
+
+				xcb::PROPERTY_NOTIFY =>
+					xcb::cast_event::<xcb::PropertyNotifyEvent>(event).time(),
+
+				xcb::SELECTION_CLEAR =>
+					xcb::cast_event::<xcb::SelectionClearEvent>(event).time(),
+
+				xcb::SELECTION_REQUEST =>
+					xcb::cast_event::<xcb::SelectionRequestEvent>(event).time(),
+
+				xcb::SELECTION_NOTIFY =>
+					xcb::cast_event::<xcb::SelectionNotifyEvent>(event).time(),
+
+				_ =>
+					return
+			}
+		};
+
+		if time == xcb::CURRENT_TIME {
+			return;
+		}
+
+		// The server time wraps around after about 49 days, so compare the
+		// difference instead of the values.
+		let last = self.timestamp();
+
+		if last == xcb::CURRENT_TIME || time.wrapping_sub(last) as i32 > 0 {
+			self.timestamp.store(time, Ordering::Relaxed);
+		}
+	}
+
+	/// Gets a server timestamp as described in ICCCM 2.1, appending nothing
+	/// to a property of the window and waiting for the `PropertyNotify`.
+	///
+	/// The window must have `EVENT_MASK_PROPERTY_CHANGE` selected and should
+	/// be a helper window nobody else touches. Any other event received while
+	/// waiting is observed and pushed to `skipped` for the caller to handle.
+	pub fn server_timestamp(&self, window: xcb::Window, timeout: Duration, skipped: &mut Vec<xcb::GenericEvent>) -> Result<xcb::Timestamp, TimestampError> {
+		let atom     = self.ext.timestamp;
+		let data: &[u32] = &[];
+		let deadline = Instant::now() + timeout;
+
+		xcb::change_property_checked(self, xcb::PROP_MODE_APPEND as u8, window,
+			atom, xcb::ATOM_INTEGER, 32, data).request_check()?;
+
+		loop {
+			let event = event::poll(self, deadline)?.ok_or(TimestampError::Timeout)?;
+			self.observe(&event);
+
+			if event.response_type() & !0x80 == xcb::PROPERTY_NOTIFY {
+				let notify: &xcb::PropertyNotifyEvent = unsafe { xcb::cast_event(&event) };
+
+				if notify.window() == window && notify.atom() == atom {
+					return Ok(notify.time());
+				}
+			}
+
+			skipped.push(event);
+		}
+	}
+
+	fn time(&self, timestamp: xcb::Timestamp) -> xcb::Timestamp {
+		if timestamp == xcb::CURRENT_TIME && self.default.load(Ordering::Relaxed) {
+			self.timestamp()
+		}
+		else {
+			timestamp
+		}
+	}
+
+	/// Decodes a client message sent to the root window into the matching
+	/// request, if it is one.
+	pub fn parse_client_request(&self, event: &xcb::ClientMessageEvent) -> Option<ClientRequest> {
+		let window = event.window();
+		let kind   = event.type_();
+		let data   = event.data().data32();
+
+		if kind == self.ewmh._NET_REQUEST_FRAME_EXTENTS {
+			return Some(ClientRequest::RequestFrameExtents {
+				window: window,
+			});
+		}
+
+		if kind == self.ext.gtk_show_window_menu && event.format() == 32 {
+			return Some(ClientRequest::ShowWindowMenu {
+				window: window,
+				device: data[0],
+				x_root: data[1] as i32,
+				y_root: data[2] as i32,
+			});
+		}
+
+		if event.format() != 32 {
+			return None;
+		}
+
+		if kind == self.ewmh._NET_WM_STATE {
+			Some(ClientRequest::WmState {
+				window: window,
+				action: data[0],
+				first:  data[1],
+				second: data[2],
+				source: data[3],
+			})
+		}
+		else if kind == self.ewmh._NET_ACTIVE_WINDOW {
+			Some(ClientRequest::ActiveWindow {
+				window:    window,
+				source:    data[0],
+				timestamp: data[1],
+				current:   data[2],
+			})
+		}
+		else if kind == self.ewmh._NET_CLOSE_WINDOW {
+			Some(ClientRequest::CloseWindow {
+				window:    window,
+				timestamp: data[0],
+				source:    data[1],
+			})
+		}
+		else if kind == self.ewmh._NET_MOVERESIZE_WINDOW {
+			Some(ClientRequest::MoveResizeWindow {
+				window:  window,
+				gravity: data[0] & 0xff,
+				flags:   data[0] & 0xf00,
+				source:  (data[0] >> 12) & 0xf,
+				x:       data[1],
+				y:       data[2],
+				width:   data[3],
+				height:  data[4],
+			})
+		}
+		else if kind == self.ewmh._NET_WM_MOVERESIZE {
+			Some(ClientRequest::WmMoveResize {
+				window:    window,
+				x_root:    data[0],
+				y_root:    data[1],
+				direction: data[2],
+				button:    data[3],
+				source:    data[4],
+			})
+		}
+		else if kind == self.ewmh._NET_CURRENT_DESKTOP {
+			Some(ClientRequest::CurrentDesktop {
+				desktop:   data[0],
+				timestamp: data[1],
+			})
+		}
+		else if kind == self.ewmh._NET_WM_DESKTOP {
+			Some(ClientRequest::WmDesktop {
+				window:  window,
+				desktop: data[0],
+				source:  data[1],
+			})
+		}
+		else if kind == self.ewmh._NET_RESTACK_WINDOW {
+			Some(ClientRequest::RestackWindow {
+				window:  window,
+				source:  data[0],
+				sibling: data[1],
+				detail:  data[2],
+			})
+		}
+		else {
+			None
+		}
+	}
+
+	/// All the EWMH atoms known to the connection, as published in
+	/// `_NET_SUPPORTED`.
+	pub fn atoms(&self) -> Vec<xcb::Atom> {
+		vec![
+			self.ewmh._NET_SUPPORTED,
+			self.ewmh._NET_CLIENT_LIST,
+			self.ewmh._NET_CLIENT_LIST_STACKING,
+			self.ewmh._NET_NUMBER_OF_DESKTOPS,
+			self.ewmh._NET_DESKTOP_GEOMETRY,
+			self.ewmh._NET_DESKTOP_VIEWPORT,
+			self.ewmh._NET_CURRENT_DESKTOP,
+			self.ewmh._NET_DESKTOP_NAMES,
+			self.ewmh._NET_ACTIVE_WINDOW,
+			self.ewmh._NET_WORKAREA,
+			self.ewmh._NET_SUPPORTING_WM_CHECK,
+			self.ewmh._NET_VIRTUAL_ROOTS,
+			self.ewmh._NET_DESKTOP_LAYOUT,
+			self.ewmh._NET_SHOWING_DESKTOP,
+			self.ewmh._NET_CLOSE_WINDOW,
+			self.ewmh._NET_MOVERESIZE_WINDOW,
+			self.ewmh._NET_WM_MOVERESIZE,
+			self.ewmh._NET_RESTACK_WINDOW,
+			self.ewmh._NET_REQUEST_FRAME_EXTENTS,
+			self.ewmh._NET_WM_NAME,
+			self.ewmh._NET_WM_VISIBLE_NAME,
+			self.ewmh._NET_WM_ICON_NAME,
+			self.ewmh._NET_WM_VISIBLE_ICON_NAME,
+			self.ewmh._NET_WM_DESKTOP,
+			self.ewmh._NET_WM_WINDOW_TYPE,
+			self.ewmh._NET_WM_STATE,
+			self.ewmh._NET_WM_ALLOWED_ACTIONS,
+			self.ewmh._NET_WM_STRUT,
+			self.ewmh._NET_WM_STRUT_PARTIAL,
+			self.ewmh._NET_WM_ICON_GEOMETRY,
+			self.ewmh._NET_WM_ICON,
+			self.ewmh._NET_WM_PID,
+			self.ewmh._NET_WM_HANDLED_ICONS,
+			self.ewmh._NET_WM_USER_TIME,
+			self.ewmh._NET_WM_USER_TIME_WINDOW,
+			self.ewmh._NET_FRAME_EXTENTS,
+			self.ewmh._NET_WM_PING,
+			self.ewmh._NET_WM_SYNC_REQUEST,
+			self.ewmh._NET_WM_SYNC_REQUEST_COUNTER,
+			self.ewmh._NET_WM_FULLSCREEN_MONITORS,
+			self.ewmh._NET_WM_FULL_PLACEMENT,
+			self.ewmh._NET_WM_WINDOW_TYPE_DESKTOP,
+			self.ewmh._NET_WM_WINDOW_TYPE_DOCK,
+			self.ewmh._NET_WM_WINDOW_TYPE_TOOLBAR,
+			self.ewmh._NET_WM_WINDOW_TYPE_MENU,
+			self.ewmh._NET_WM_WINDOW_TYPE_UTILITY,
+			self.ewmh._NET_WM_WINDOW_TYPE_SPLASH,
+			self.ewmh._NET_WM_WINDOW_TYPE_DIALOG,
+			self.ewmh._NET_WM_WINDOW_TYPE_DROPDOWN_MENU,
+			self.ewmh._NET_WM_WINDOW_TYPE_POPUP_MENU,
+			self.ewmh._NET_WM_WINDOW_TYPE_TOOLTIP,
+			self.ewmh._NET_WM_WINDOW_TYPE_NOTIFICATION,
+			self.ewmh._NET_WM_WINDOW_TYPE_COMBO,
+			self.ewmh._NET_WM_WINDOW_TYPE_DND,
+			self.ewmh._NET_WM_WINDOW_TYPE_NORMAL,
+			self.ewmh._NET_WM_STATE_MODAL,
+			self.ewmh._NET_WM_STATE_STICKY,
+			self.ewmh._NET_WM_STATE_MAXIMIZED_VERT,
+			self.ewmh._NET_WM_STATE_MAXIMIZED_HORZ,
+			self.ewmh._NET_WM_STATE_SHADED,
+			self.ewmh._NET_WM_STATE_SKIP_TASKBAR,
+			self.ewmh._NET_WM_STATE_SKIP_PAGER,
+			self.ewmh._NET_WM_STATE_HIDDEN,
+			self.ewmh._NET_WM_STATE_FULLSCREEN,
+			self.ewmh._NET_WM_STATE_ABOVE,
+			self.ewmh._NET_WM_STATE_BELOW,
+			self.ewmh._NET_WM_STATE_DEMANDS_ATTENTION,
+			self.ewmh._NET_WM_ACTION_MOVE,
+			self.ewmh._NET_WM_ACTION_RESIZE,
+			self.ewmh._NET_WM_ACTION_MINIMIZE,
+			self.ewmh._NET_WM_ACTION_SHADE,
+			self.ewmh._NET_WM_ACTION_STICK,
+			self.ewmh._NET_WM_ACTION_MAXIMIZE_HORZ,
+			self.ewmh._NET_WM_ACTION_MAXIMIZE_VERT,
+			self.ewmh._NET_WM_ACTION_FULLSCREEN,
+			self.ewmh._NET_WM_ACTION_CHANGE_DESKTOP,
+			self.ewmh._NET_WM_ACTION_CLOSE,
+			self.ewmh._NET_WM_ACTION_ABOVE,
+			self.ewmh._NET_WM_ACTION_BELOW,
+		]
+	}
+
 	#[inline(always)]
 	pub fn WM_CM(&self) -> &[xcb::Atom] {
 		unsafe {
@@ -470,6 +1399,11 @@ impl Connection {
 		self.ewmh._NET_WM_FULL_PLACEMENT
 	}
 
+	#[inline(always)]
+	pub fn UTF8_STRING(&self) -> xcb::Atom {
+		self.ewmh.UTF8_STRING
+	}
+
 	#[inline(always)]
 	pub fn WM_PROTOCOLS(&self) -> xcb::Atom {
 		self.ewmh.WM_PROTOCOLS
@@ -669,6 +1603,42 @@ impl Connection {
 	pub fn WM_ACTION_BELOW(&self) -> xcb::Atom {
 		self.ewmh._NET_WM_ACTION_BELOW
 	}
+
+	#[inline(always)]
+	pub fn WM_WINDOW_OPACITY(&self) -> xcb::Atom {
+		self.ext.wm_window_opacity
+	}
+
+	#[inline(always)]
+	pub fn WM_OPAQUE_REGION(&self) -> xcb::Atom {
+		self.ext.wm_opaque_region
+	}
+
+	#[inline(always)]
+	pub fn WM_BYPASS_COMPOSITOR(&self) -> xcb::Atom {
+		self.ext.wm_bypass_compositor
+	}
+
+	#[inline(always)]
+	pub fn WM_STATE_FOCUSED(&self) -> xcb::Atom {
+		self.ext.wm_state_focused
+	}
+
+	#[inline(always)]
+	pub fn GTK_FRAME_EXTENTS(&self) -> xcb::Atom {
+		self.ext.gtk_frame_extents
+	}
+
+	#[inline(always)]
+	pub fn GTK_SHOW_WINDOW_MENU(&self) -> xcb::Atom {
+		self.ext.gtk_show_window_menu
+	}
+
+	/// For `icccm::get_motif_hints` and `icccm::set_motif_hints`.
+	#[inline(always)]
+	pub fn MOTIF_WM_HINTS(&self) -> xcb::Atom {
+		self.ext.motif_wm_hints
+	}
 }
 
 impl Deref for Connection {
@@ -679,6 +1649,9 @@ impl Deref for Connection {
 	}
 }
 
+// A `Connection` always owns its `xcb::Connection`, the aliases behind
+// `BorrowedConnection` and `SharedConnection` are only reachable through
+// `Deref`.
 impl DerefMut for Connection {
 	fn deref_mut(&mut self) -> &mut Self::Target {
 		&mut self.xcb
@@ -693,26 +1666,123 @@ impl Drop for Connection {
 	}
 }
 
+/// A `Connection` over a borrowed `xcb::Connection`, the atoms are wiped when
+/// dropped but the connection is left alone.
+pub struct BorrowedConnection<'a> {
+	conn:   mem::ManuallyDrop<Connection>,
+	marker: PhantomData<&'a xcb::Connection>,
+}
+
+impl<'a> Drop for BorrowedConnection<'a> {
+	fn drop(&mut self) {
+		unsafe {
+			xcb_ewmh_connection_wipe(&mut self.conn.ewmh)
+		}
+	}
+}
+
+impl<'a> Deref for BorrowedConnection<'a> {
+	type Target = Connection;
+
+	fn deref(&self) -> &Self::Target {
+		&self.conn
+	}
+}
+
+/// A `Connection` over an `xcb::Connection` shared with other users, the
+/// atoms are wiped when dropped before the reference is released.
+pub struct SharedConnection {
+	conn: mem::ManuallyDrop<Connection>,
+	xcb:  Arc<xcb::Connection>,
+}
+
+#[cfg(feature = "thread")]
+unsafe impl Send for SharedConnection { }
+#[cfg(feature = "thread")]
+unsafe impl Sync for SharedConnection { }
+
+impl SharedConnection {
+	/// Wipes the atoms and gives back the shared connection.
+	pub fn into_inner(mut self) -> Arc<xcb::Connection> {
+		unsafe {
+			xcb_ewmh_connection_wipe(&mut self.conn.ewmh);
+
+			let xcb = ptr::read(&self.xcb);
+			mem::forget(self);
+
+			xcb
+		}
+	}
+}
+
+impl Drop for SharedConnection {
+	fn drop(&mut self) {
+		unsafe {
+			xcb_ewmh_connection_wipe(&mut self.conn.ewmh)
+		}
+	}
+}
+
+impl Deref for SharedConnection {
+	type Target = Connection;
+
+	fn deref(&self) -> &Self::Target {
+		&self.conn
+	}
+}
+
 pub fn send_client_message<'a>(c: &'a xcb::Connection, window: xcb::Window, dest: xcb::Window, atom: xcb::Atom, data: &[u32]) -> xcb::VoidCookie<'a> {
 	void!(unchecked -> c,
 		xcb_ewmh_send_client_message(c.get_raw_conn(), window, dest, atom,
 			data.len() as u32, data.as_ptr()))
 }
 
-pub fn request_close_window(c: &Connection, screen: i32, window: xcb::Window, timestamp: xcb::Timestamp, source_indication: ClientSourceType) -> xcb::VoidCookie {
+/// Sends the client message to `dest` itself without an event mask, so it
+/// reaches the client that created the window instead of whoever selected
+/// substructure events on it, as XDND and the system tray expect.
+///
+/// At most five items of data are sent.
+pub fn send_client_message_direct<'a>(c: &'a xcb::Connection, window: xcb::Window, dest: xcb::Window, atom: xcb::Atom, data: &[u32]) -> xcb::VoidCookie<'a> {
+	let mut items = [0u32; 5];
+
+	for (item, &value) in items.iter_mut().zip(data) {
+		*item = value;
+	}
+
+	let event = xcb::ClientMessageEvent::new(32, window, atom, xcb::ClientMessageData::from_data32(items));
+	xcb::send_event(c, false, dest, xcb::EVENT_MASK_NO_EVENT, &event)
+}
+
+pub fn request_close_window(c: &Connection, screen: Screen, window: xcb::Window, timestamp: xcb::Timestamp, source_indication: ClientSourceType) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_request_close_window(c.get_raw_conn(), screen as c_int, window, timestamp, source_indication))
+		xcb_ewmh_request_close_window(c.get_raw_conn(), screen.index() as c_int, window, c.time(timestamp), source_indication))
 }
 
-pub fn request_move_resize_window(c: &Connection, screen: i32, window: xcb::Window, gravity: xcb::Gravity, source_indication: ClientSourceType, flags: MoveResizeWindowFlags, x: u32, y: u32, width: u32, height: u32) -> xcb::VoidCookie {
+pub fn request_move_resize_window(c: &Connection, screen: Screen, window: xcb::Window, gravity: xcb::Gravity, source_indication: ClientSourceType, flags: MoveResizeWindowFlags, x: u32, y: u32, width: u32, height: u32) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_request_moveresize_window(c.get_raw_conn(), screen as c_int, window,
+		xcb_ewmh_request_moveresize_window(c.get_raw_conn(), screen.index() as c_int, window,
 			gravity, source_indication, flags, x, y, width, height))
 }
 
+pub fn request_wm_move_resize(c: &Connection, screen: Screen, window: xcb::Window, x_root: u32, y_root: u32, direction: MoveResizeDirection, button: xcb::ButtonIndex, source_indication: ClientSourceType) -> xcb::VoidCookie {
+	void!(unchecked -> c,
+		xcb_ewmh_request_wm_moveresize(c.get_raw_conn(), screen.index() as c_int, window,
+			x_root, y_root, direction, button, source_indication))
+}
+
+pub fn request_restack_window(c: &Connection, screen: Screen, window: xcb::Window, sibling: xcb::Window, detail: xcb::StackMode) -> xcb::VoidCookie {
+	void!(unchecked -> c,
+		xcb_ewmh_request_restack_window(c.get_raw_conn(), screen.index() as c_int, window, sibling, detail))
+}
+
+pub fn request_frame_extents(c: &Connection, screen: Screen, window: xcb::Window) -> xcb::VoidCookie {
+	void!(unchecked -> c,
+		xcb_ewmh_request_frame_extents(c.get_raw_conn(), screen.index() as c_int, window))
+}
+
 pub fn send_wm_ping(c: &Connection, window: xcb::Window, timestamp: xcb::Timestamp) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_send_wm_ping(c.get_raw_conn(), window, timestamp))
+		xcb_ewmh_send_wm_ping(c.get_raw_conn(), window, c.time(timestamp)))
 }
 
 define!(cookie GetSupportedCookie through Connection with xcb_ewmh_get_supported_reply => GetSupportedReply);
@@ -726,26 +1796,26 @@ impl GetSupportedReply {
 	}
 }
 
-pub fn set_supported<'a>(c: &'a Connection, screen: i32, list: &[xcb::Atom]) -> xcb::VoidCookie<'a> {
+pub fn set_supported<'a>(c: &'a Connection, screen: Screen, list: &[xcb::Atom]) -> xcb::VoidCookie<'a> {
 	void!(unchecked -> c,
-		xcb_ewmh_set_supported(c.get_raw_conn(), screen as c_int,
+		xcb_ewmh_set_supported(c.get_raw_conn(), screen.index() as c_int,
 			list.len() as u32, list.as_ptr()))
 }
 
-pub fn set_supported_checked<'a>(c: &'a Connection, screen: i32, list: &[xcb::Atom]) -> xcb::VoidCookie<'a> {
+pub fn set_supported_checked<'a>(c: &'a Connection, screen: Screen, list: &[xcb::Atom]) -> xcb::VoidCookie<'a> {
 	void!(checked -> c,
-		xcb_ewmh_set_supported_checked(c.get_raw_conn(), screen as c_int,
+		xcb_ewmh_set_supported_checked(c.get_raw_conn(), screen.index() as c_int,
 			list.len() as u32, list.as_ptr()))
 }
 
-pub fn get_supported(c: &Connection, screen: i32) -> GetSupportedCookie {
+pub fn get_supported(c: &Connection, screen: Screen) -> GetSupportedCookie {
 	property!(checked GetSupportedCookie -> c,
-		xcb_ewmh_get_supported(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_supported(c.get_raw_conn(), screen.index() as c_int))
 }
 
-pub fn get_supported_unchecked(c: &Connection, screen: i32) -> GetSupportedCookie {
+pub fn get_supported_unchecked(c: &Connection, screen: Screen) -> GetSupportedCookie {
 	property!(unchecked GetSupportedCookie -> c,
-		xcb_ewmh_get_supported_unchecked(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_supported_unchecked(c.get_raw_conn(), screen.index() as c_int))
 }
 
 define!(cookie GetClientListCookie through Connection with xcb_ewmh_get_client_list_reply => GetClientListReply);
@@ -759,26 +1829,26 @@ impl GetClientListReply {
 	}
 }
 
-pub fn set_client_list<'a>(c: &'a Connection, screen: i32, list: &[xcb::Window]) -> xcb::VoidCookie<'a> {
+pub fn set_client_list<'a>(c: &'a Connection, screen: Screen, list: &[xcb::Window]) -> xcb::VoidCookie<'a> {
 	void!(unchecked -> c,
-		xcb_ewmh_set_client_list(c.get_raw_conn(), screen as c_int,
+		xcb_ewmh_set_client_list(c.get_raw_conn(), screen.index() as c_int,
 			list.len() as u32, list.as_ptr() as *const _))
 }
 
-pub fn set_client_list_checked<'a>(c: &'a Connection, screen: i32, list: &[xcb::Window]) -> xcb::VoidCookie<'a> {
+pub fn set_client_list_checked<'a>(c: &'a Connection, screen: Screen, list: &[xcb::Window]) -> xcb::VoidCookie<'a> {
 	void!(checked -> c,
-		xcb_ewmh_set_client_list_checked(c.get_raw_conn(), screen as c_int,
+		xcb_ewmh_set_client_list_checked(c.get_raw_conn(), screen.index() as c_int,
 			list.len() as u32, list.as_ptr() as *const _))
 }
 
-pub fn get_client_list(c: &Connection, screen: i32) -> GetClientListCookie {
+pub fn get_client_list(c: &Connection, screen: Screen) -> GetClientListCookie {
 	property!(checked GetClientListCookie -> c,
-		xcb_ewmh_get_client_list(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_client_list(c.get_raw_conn(), screen.index() as c_int))
 }
 
-pub fn get_client_list_unchecked(c: &Connection, screen: i32) -> GetClientListCookie {
+pub fn get_client_list_unchecked(c: &Connection, screen: Screen) -> GetClientListCookie {
 	property!(unchecked GetClientListCookie -> c,
-		xcb_ewmh_get_client_list(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_client_list_unchecked(c.get_raw_conn(), screen.index() as c_int))
 }
 
 define!(cookie GetClientListStackingCookie through Connection with xcb_ewmh_get_client_list_stacking_reply => GetClientListStackingReply);
@@ -792,75 +1862,75 @@ impl GetClientListStackingReply {
 	}
 }
 
-pub fn set_client_list_stacking<'a>(c: &'a Connection, screen: i32, list: &[xcb::Window]) -> xcb::VoidCookie<'a> {
+pub fn set_client_list_stacking<'a>(c: &'a Connection, screen: Screen, list: &[xcb::Window]) -> xcb::VoidCookie<'a> {
 	void!(unchecked -> c,
-		xcb_ewmh_set_client_list_stacking(c.get_raw_conn(), screen as c_int,
+		xcb_ewmh_set_client_list_stacking(c.get_raw_conn(), screen.index() as c_int,
 			list.len() as u32, list.as_ptr() as *const _))
 }
 
-pub fn set_client_list_stacking_checked<'a>(c: &'a Connection, screen: i32, list: &[xcb::Window]) -> xcb::VoidCookie<'a> {
+pub fn set_client_list_stacking_checked<'a>(c: &'a Connection, screen: Screen, list: &[xcb::Window]) -> xcb::VoidCookie<'a> {
 	void!(checked -> c,
-		xcb_ewmh_set_client_list_stacking_checked(c.get_raw_conn(), screen as c_int,
+		xcb_ewmh_set_client_list_stacking_checked(c.get_raw_conn(), screen.index() as c_int,
 			list.len() as u32, list.as_ptr() as *const _))
 }
 
-pub fn get_client_list_stacking(c: &Connection, screen: i32) -> GetClientListStackingCookie {
+pub fn get_client_list_stacking(c: &Connection, screen: Screen) -> GetClientListStackingCookie {
 	property!(checked GetClientListStackingCookie -> c,
-		xcb_ewmh_get_client_list_stacking(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_client_list_stacking(c.get_raw_conn(), screen.index() as c_int))
 }
 
-pub fn get_client_list_stacking_unchecked(c: &Connection, screen: i32) -> GetClientListStackingCookie {
+pub fn get_client_list_stacking_unchecked(c: &Connection, screen: Screen) -> GetClientListStackingCookie {
 	property!(unchecked GetClientListStackingCookie -> c,
-		xcb_ewmh_get_client_list_stacking(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_client_list_stacking_unchecked(c.get_raw_conn(), screen.index() as c_int))
 }
 
 define!(cookie GetNumberOfDesktopsCookie through Connection with xcb_ewmh_get_number_of_desktops_reply as u32);
 
-pub fn set_number_of_desktops(c: &Connection, screen: i32, number: u32) -> xcb::VoidCookie {
+pub fn set_number_of_desktops(c: &Connection, screen: Screen, number: u32) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_set_number_of_desktops(c.get_raw_conn(), screen as c_int, number))
+		xcb_ewmh_set_number_of_desktops(c.get_raw_conn(), screen.index() as c_int, number))
 }
 
-pub fn set_number_of_desktops_checked(c: &Connection, screen: i32, number: u32) -> xcb::VoidCookie {
+pub fn set_number_of_desktops_checked(c: &Connection, screen: Screen, number: u32) -> xcb::VoidCookie {
 	void!(checked -> c,
-		xcb_ewmh_set_number_of_desktops_checked(c.get_raw_conn(), screen as c_int, number))
+		xcb_ewmh_set_number_of_desktops_checked(c.get_raw_conn(), screen.index() as c_int, number))
 }
 
-pub fn get_number_of_desktops(c: &Connection, screen: i32) -> GetNumberOfDesktopsCookie {
+pub fn get_number_of_desktops(c: &Connection, screen: Screen) -> GetNumberOfDesktopsCookie {
 	property!(checked GetNumberOfDesktopsCookie -> c,
-		xcb_ewmh_get_number_of_desktops(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_number_of_desktops(c.get_raw_conn(), screen.index() as c_int))
 }
 
-pub fn get_number_of_desktops_unchecked(c: &Connection, screen: i32) -> GetNumberOfDesktopsCookie {
+pub fn get_number_of_desktops_unchecked(c: &Connection, screen: Screen) -> GetNumberOfDesktopsCookie {
 	property!(unchecked GetNumberOfDesktopsCookie -> c,
-		xcb_ewmh_get_number_of_desktops_unchecked(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_number_of_desktops_unchecked(c.get_raw_conn(), screen.index() as c_int))
 }
 
 define!(cookie GetDesktopGeometryCookie through Connection with xcb_ewmh_get_desktop_geometry_reply as (u32, u32));
 
-pub fn set_desktop_geometry(c: &Connection, screen: i32, width: u32, height: u32) -> xcb::VoidCookie {
+pub fn set_desktop_geometry(c: &Connection, screen: Screen, width: u32, height: u32) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_set_desktop_geometry(c.get_raw_conn(), screen as c_int, width, height))
+		xcb_ewmh_set_desktop_geometry(c.get_raw_conn(), screen.index() as c_int, width, height))
 }
 
-pub fn set_desktop_geometry_checked(c: &Connection, screen: i32, width: u32, height: u32) -> xcb::VoidCookie {
+pub fn set_desktop_geometry_checked(c: &Connection, screen: Screen, width: u32, height: u32) -> xcb::VoidCookie {
 	void!(checked -> c,
-		xcb_ewmh_set_desktop_geometry_checked(c.get_raw_conn(), screen as c_int, width, height))
+		xcb_ewmh_set_desktop_geometry_checked(c.get_raw_conn(), screen.index() as c_int, width, height))
 }
 
-pub fn request_change_desktop_geometry(c: &Connection, screen: i32, width: u32, height: u32) -> xcb::VoidCookie {
+pub fn request_change_desktop_geometry(c: &Connection, screen: Screen, width: u32, height: u32) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_request_change_desktop_geometry(c.get_raw_conn(), screen as c_int, width, height))
+		xcb_ewmh_request_change_desktop_geometry(c.get_raw_conn(), screen.index() as c_int, width, height))
 }
 
-pub fn get_desktop_geometry(c: &Connection, screen: i32) -> GetDesktopGeometryCookie {
+pub fn get_desktop_geometry(c: &Connection, screen: Screen) -> GetDesktopGeometryCookie {
 	property!(checked GetDesktopGeometryCookie -> c,
-		xcb_ewmh_get_desktop_geometry(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_desktop_geometry(c.get_raw_conn(), screen.index() as c_int))
 }
 
-pub fn get_desktop_geometry_unchecked(c: &Connection, screen: i32) -> GetDesktopGeometryCookie {
+pub fn get_desktop_geometry_unchecked(c: &Connection, screen: Screen) -> GetDesktopGeometryCookie {
 	property!(unchecked GetDesktopGeometryCookie -> c,
-		xcb_ewmh_get_desktop_geometry_unchecked(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_desktop_geometry_unchecked(c.get_raw_conn(), screen.index() as c_int))
 }
 
 define!(cookie GetDesktopViewportCookie through Connection with xcb_ewmh_get_desktop_viewport_reply => GetDesktopViewportReply);
@@ -874,53 +1944,53 @@ impl GetDesktopViewportReply {
 	}
 }
 
-pub fn set_desktop_viewport<'a>(c: &'a Connection, screen: i32, list: &[Coordinates]) -> xcb::VoidCookie<'a> {
+pub fn set_desktop_viewport<'a>(c: &'a Connection, screen: Screen, list: &[Coordinates]) -> xcb::VoidCookie<'a> {
 	void!(unchecked -> c,
-		xcb_ewmh_set_desktop_viewport(c.get_raw_conn(), screen as c_int,
+		xcb_ewmh_set_desktop_viewport(c.get_raw_conn(), screen.index() as c_int,
 			list.len() as u32, list.as_ptr()))
 }
 
-pub fn set_desktop_viewport_checked<'a>(c: &'a Connection, screen: i32, list: &[Coordinates]) -> xcb::VoidCookie<'a> {
+pub fn set_desktop_viewport_checked<'a>(c: &'a Connection, screen: Screen, list: &[Coordinates]) -> xcb::VoidCookie<'a> {
 	void!(checked -> c,
-		xcb_ewmh_set_desktop_viewport_checked(c.get_raw_conn(), screen as c_int,
+		xcb_ewmh_set_desktop_viewport_checked(c.get_raw_conn(), screen.index() as c_int,
 			list.len() as u32, list.as_ptr()))
 }
 
-pub fn get_desktop_viewport(c: &Connection, screen: i32) -> GetDesktopViewportCookie {
+pub fn get_desktop_viewport(c: &Connection, screen: Screen) -> GetDesktopViewportCookie {
 	property!(checked GetDesktopViewportCookie -> c,
-		xcb_ewmh_get_desktop_viewport(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_desktop_viewport(c.get_raw_conn(), screen.index() as c_int))
 }
 
-pub fn get_desktop_viewport_unchecked(c: &Connection, screen: i32) -> GetDesktopViewportCookie {
+pub fn get_desktop_viewport_unchecked(c: &Connection, screen: Screen) -> GetDesktopViewportCookie {
 	property!(unchecked GetDesktopViewportCookie -> c,
-		xcb_ewmh_get_desktop_viewport_unchecked(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_desktop_viewport_unchecked(c.get_raw_conn(), screen.index() as c_int))
 }
 
 define!(cookie GetCurrentDesktopCookie through Connection with xcb_ewmh_get_current_desktop_reply as u32);
 
-pub fn set_current_desktop(c: &Connection, screen: i32, current_desktop: u32) -> xcb::VoidCookie {
+pub fn set_current_desktop(c: &Connection, screen: Screen, current_desktop: u32) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_set_current_desktop(c.get_raw_conn(), screen as c_int, current_desktop))
+		xcb_ewmh_set_current_desktop(c.get_raw_conn(), screen.index() as c_int, current_desktop))
 }
 
-pub fn set_current_desktop_checked(c: &Connection, screen: i32, current_desktop: u32) -> xcb::VoidCookie {
+pub fn set_current_desktop_checked(c: &Connection, screen: Screen, current_desktop: u32) -> xcb::VoidCookie {
 	void!(checked -> c,
-		xcb_ewmh_set_current_desktop_checked(c.get_raw_conn(), screen as c_int, current_desktop))
+		xcb_ewmh_set_current_desktop_checked(c.get_raw_conn(), screen.index() as c_int, current_desktop))
 }
 
-pub fn request_change_current_desktop(c: &Connection, screen: i32, current_desktop: u32, timestamp: xcb::Timestamp) -> xcb::VoidCookie {
+pub fn request_change_current_desktop(c: &Connection, screen: Screen, current_desktop: u32, timestamp: xcb::Timestamp) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_request_change_current_desktop(c.get_raw_conn(), screen as c_int, current_desktop, timestamp))
+		xcb_ewmh_request_change_current_desktop(c.get_raw_conn(), screen.index() as c_int, current_desktop, c.time(timestamp)))
 }
 
-pub fn get_current_desktop(c: &Connection, screen: i32) -> GetCurrentDesktopCookie {
+pub fn get_current_desktop(c: &Connection, screen: Screen) -> GetCurrentDesktopCookie {
 	property!(checked GetCurrentDesktopCookie -> c,
-		xcb_ewmh_get_current_desktop(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_current_desktop(c.get_raw_conn(), screen.index() as c_int))
 }
 
-pub fn get_current_desktop_unchecked(c: &Connection, screen: i32) -> GetCurrentDesktopCookie {
+pub fn get_current_desktop_unchecked(c: &Connection, screen: Screen) -> GetCurrentDesktopCookie {
 	property!(unchecked GetCurrentDesktopCookie -> c,
-		xcb_ewmh_get_current_desktop_unchecked(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_current_desktop_unchecked(c.get_raw_conn(), screen.index() as c_int))
 }
 
 define!(cookie GetDesktopNamesCookie through Connection with xcb_ewmh_get_desktop_names_reply => GetDesktopNamesReply);
@@ -932,56 +2002,56 @@ impl GetDesktopNamesReply {
 	}
 }
 
-pub fn set_desktop_names<'a, T: IntoIterator<Item=&'a str>>(c: &Connection, screen: i32, list: T) -> xcb::VoidCookie {
+pub fn set_desktop_names<'a, T: IntoIterator<Item=&'a str>>(c: &Connection, screen: Screen, list: T) -> xcb::VoidCookie {
 	let value = utf8::from(list);
 
 	void!(unchecked -> c,
-		xcb_ewmh_set_desktop_names(c.get_raw_conn(), screen, value.len() as u32, value.as_ptr() as *mut _))
+		xcb_ewmh_set_desktop_names(c.get_raw_conn(), screen.index() as c_int, value.len() as u32, value.as_ptr() as *mut _))
 }
 
-pub fn set_desktop_names_checked<'a, T: IntoIterator<Item=&'a str>>(c: &Connection, screen: i32, list: T) -> xcb::VoidCookie {
+pub fn set_desktop_names_checked<'a, T: IntoIterator<Item=&'a str>>(c: &Connection, screen: Screen, list: T) -> xcb::VoidCookie {
 	let value = utf8::from(list);
 
 	void!(checked -> c,
-		xcb_ewmh_set_desktop_names_checked(c.get_raw_conn(), screen, value.len() as u32, value.as_ptr() as *mut _))
+		xcb_ewmh_set_desktop_names_checked(c.get_raw_conn(), screen.index() as c_int, value.len() as u32, value.as_ptr() as *mut _))
 }
 
-pub fn get_desktop_names(c: &Connection, screen: i32) -> GetDesktopNamesCookie {
+pub fn get_desktop_names(c: &Connection, screen: Screen) -> GetDesktopNamesCookie {
 	property!(checked GetDesktopNamesCookie -> c,
-		xcb_ewmh_get_desktop_names(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_desktop_names(c.get_raw_conn(), screen.index() as c_int))
 }
 
-pub fn get_desktop_names_unchecked(c: &Connection, screen: i32) -> GetDesktopNamesCookie {
+pub fn get_desktop_names_unchecked(c: &Connection, screen: Screen) -> GetDesktopNamesCookie {
 	property!(unchecked GetDesktopNamesCookie -> c,
-		xcb_ewmh_get_desktop_names_unchecked(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_desktop_names_unchecked(c.get_raw_conn(), screen.index() as c_int))
 }
 
 define!(cookie GetActiveWindowCookie through Connection with xcb_ewmh_get_active_window_reply as xcb::Window);
 
-pub fn set_active_window(c: &Connection, screen: i32, window: xcb::Window) -> xcb::VoidCookie {
+pub fn set_active_window(c: &Connection, screen: Screen, window: xcb::Window) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_set_active_window(c.get_raw_conn(), screen as c_int, window))
+		xcb_ewmh_set_active_window(c.get_raw_conn(), screen.index() as c_int, window))
 }
 
-pub fn set_active_window_checked(c: &Connection, screen: i32, window: xcb::Window) -> xcb::VoidCookie {
+pub fn set_active_window_checked(c: &Connection, screen: Screen, window: xcb::Window) -> xcb::VoidCookie {
 	void!(checked -> c,
-		xcb_ewmh_set_active_window_checked(c.get_raw_conn(), screen as c_int, window))
+		xcb_ewmh_set_active_window_checked(c.get_raw_conn(), screen.index() as c_int, window))
 }
 
-pub fn request_change_active_window(c: &Connection, screen: i32, window: xcb::Window, source_indication: ClientSourceType, timestamp: xcb::Timestamp, current: xcb::Window) -> xcb::VoidCookie {
+pub fn request_change_active_window(c: &Connection, screen: Screen, window: xcb::Window, source_indication: ClientSourceType, timestamp: xcb::Timestamp, current: xcb::Window) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_request_change_active_window(c.get_raw_conn(), screen as c_int, window,
-			source_indication, timestamp, current))
+		xcb_ewmh_request_change_active_window(c.get_raw_conn(), screen.index() as c_int, window,
+			source_indication, c.time(timestamp), current))
 }
 
-pub fn get_active_window(c: &Connection, screen: i32) -> GetActiveWindowCookie {
+pub fn get_active_window(c: &Connection, screen: Screen) -> GetActiveWindowCookie {
 	property!(checked GetActiveWindowCookie -> c,
-		xcb_ewmh_get_active_window(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_active_window(c.get_raw_conn(), screen.index() as c_int))
 }
 
-pub fn get_active_window_unchecked(c: &Connection, screen: i32) -> GetActiveWindowCookie {
+pub fn get_active_window_unchecked(c: &Connection, screen: Screen) -> GetActiveWindowCookie {
 	property!(unchecked GetActiveWindowCookie -> c,
-		xcb_ewmh_get_active_window_unchecked(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_active_window_unchecked(c.get_raw_conn(), screen.index() as c_int))
 }
 
 define!(cookie GetWorkAreaCookie through Connection with xcb_ewmh_get_workarea_reply => GetWorkAreaReply);
@@ -995,24 +2065,24 @@ impl GetWorkAreaReply {
 	}
 }
 
-pub fn set_work_area<'a>(c: &'a Connection, screen: i32, list: &[Geometry]) -> xcb::VoidCookie<'a> {
+pub fn set_work_area<'a>(c: &'a Connection, screen: Screen, list: &[Geometry]) -> xcb::VoidCookie<'a> {
 	void!(unchecked -> c,
-		xcb_ewmh_set_workarea(c.get_raw_conn(), screen as c_int, list.len() as u32, list.as_ptr()))
+		xcb_ewmh_set_workarea(c.get_raw_conn(), screen.index() as c_int, list.len() as u32, list.as_ptr()))
 }
 
-pub fn set_work_area_checked<'a>(c: &'a Connection, screen: i32, list: &[Geometry]) -> xcb::VoidCookie<'a> {
+pub fn set_work_area_checked<'a>(c: &'a Connection, screen: Screen, list: &[Geometry]) -> xcb::VoidCookie<'a> {
 	void!(checked -> c,
-		xcb_ewmh_set_workarea_checked(c.get_raw_conn(), screen as c_int, list.len() as u32, list.as_ptr()))
+		xcb_ewmh_set_workarea_checked(c.get_raw_conn(), screen.index() as c_int, list.len() as u32, list.as_ptr()))
 }
 
-pub fn get_work_area(c: &Connection, screen: i32) -> GetWorkAreaCookie {
+pub fn get_work_area(c: &Connection, screen: Screen) -> GetWorkAreaCookie {
 	property!(checked GetWorkAreaCookie -> c,
-		xcb_ewmh_get_workarea(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_workarea(c.get_raw_conn(), screen.index() as c_int))
 }
 
-pub fn get_work_area_unchecked(c: &Connection, screen: i32) -> GetWorkAreaCookie {
+pub fn get_work_area_unchecked(c: &Connection, screen: Screen) -> GetWorkAreaCookie {
 	property!(unchecked GetWorkAreaCookie -> c,
-		xcb_ewmh_get_workarea_unchecked(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_workarea_unchecked(c.get_raw_conn(), screen.index() as c_int))
 }
 
 define!(cookie GetSupportingWmCheckCookie through Connection with xcb_ewmh_get_supporting_wm_check_reply as xcb::Window);
@@ -1037,27 +2107,172 @@ pub fn get_supporting_wm_check_unchecked(c: &Connection, window: xcb::Window) ->
 		xcb_ewmh_get_supporting_wm_check_unchecked(c.get_raw_conn(), window))
 }
 
+/// The root window properties a window manager publishes on a screen to be
+/// recognized as EWMH compliant, they are removed when dropped.
+pub struct WmAnnouncement<'a> {
+	conn:   &'a Connection,
+	screen: Screen,
+	window: xcb::Window,
+}
+
+/// Why publishing a `WmAnnouncement` failed.
+#[derive(Debug)]
+pub enum AnnounceError {
+	/// The current desktop is not below the number of desktops.
+	InvalidDesktop(u32),
+
+	Reply(xcb::ReplyError),
+}
+
+impl fmt::Display for AnnounceError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			AnnounceError::InvalidDesktop(desktop) =>
+				write!(f, "invalid current desktop {}", desktop),
+
+			AnnounceError::Reply(ref err) =>
+				err.fmt(f),
+		}
+	}
+}
+
+impl error::Error for AnnounceError { }
+
+impl From<xcb::ReplyError> for AnnounceError {
+	fn from(value: xcb::ReplyError) -> Self {
+		AnnounceError::Reply(value)
+	}
+}
+
+pub struct WmAnnouncementBuilder {
+	name:      String,
+	supported: Option<Vec<xcb::Atom>>,
+	desktops:  Option<u32>,
+	names:     Vec<String>,
+	current:   u32,
+}
+
+impl<'a> WmAnnouncement<'a> {
+	pub fn named<T: AsRef<str>>(name: T) -> WmAnnouncementBuilder {
+		WmAnnouncementBuilder {
+			name:      name.as_ref().to_owned(),
+			supported: None,
+			desktops:  None,
+			names:     Vec::new(),
+			current:   0,
+		}
+	}
+
+	pub fn screen(&self) -> Screen {
+		self.screen
+	}
+
+	pub fn root(&self) -> xcb::Window {
+		self.screen.root()
+	}
+
+	/// The `_NET_SUPPORTING_WM_CHECK` child window.
+	pub fn window(&self) -> xcb::Window {
+		self.window
+	}
+}
+
+impl<'a> Drop for WmAnnouncement<'a> {
+	fn drop(&mut self) {
+		let root = self.screen.root();
+
+		xcb::delete_property(self.conn, root, self.conn.SUPPORTING_WM_CHECK());
+		xcb::delete_property(self.conn, root, self.conn.SUPPORTED());
+		xcb::delete_property(self.conn, root, self.conn.NUMBER_OF_DESKTOPS());
+		xcb::delete_property(self.conn, root, self.conn.DESKTOP_NAMES());
+		xcb::delete_property(self.conn, root, self.conn.CURRENT_DESKTOP());
+		xcb::destroy_window(self.conn, self.window);
+		self.conn.flush();
+	}
+}
+
+impl WmAnnouncementBuilder {
+	/// Overrides the atoms published in `_NET_SUPPORTED`, by default every
+	/// atom known to the connection is advertised.
+	pub fn supported(mut self, atoms: &[xcb::Atom]) -> Self {
+		self.supported = Some(atoms.to_vec());
+		self
+	}
+
+	/// Sets `_NET_NUMBER_OF_DESKTOPS`, by default the number of desktop names
+	/// or one if there are none.
+	pub fn desktops(mut self, number: u32) -> Self {
+		self.desktops = Some(number);
+		self
+	}
+
+	pub fn desktop_names<T: AsRef<str>, I: IntoIterator<Item=T>>(mut self, names: I) -> Self {
+		self.names = names.into_iter().map(|n| n.as_ref().to_owned()).collect();
+		self
+	}
+
+	pub fn current_desktop(mut self, desktop: u32) -> Self {
+		self.current = desktop;
+		self
+	}
+
+	/// Creates the check window and publishes everything on the root window of
+	/// the given screen.
+	pub fn announce(self, c: &Connection, screen: Screen) -> Result<WmAnnouncement, AnnounceError> {
+		let root = screen.root();
+
+		let desktops = self.desktops.unwrap_or(cmp::max(1, self.names.len() as u32));
+
+		if self.current >= desktops {
+			return Err(AnnounceError::InvalidDesktop(self.current));
+		}
+
+		let window = c.generate_id();
+		xcb::create_window_checked(c, xcb::COPY_FROM_PARENT as u8, window, root,
+			-1, -1, 1, 1, 0, xcb::WINDOW_CLASS_INPUT_ONLY as u16, xcb::COPY_FROM_PARENT,
+			&[(xcb::CW_OVERRIDE_REDIRECT, 1)]).request_check()?;
+
+		let announcement = WmAnnouncement {
+			conn:   c,
+			screen: screen,
+			window: window,
+		};
+
+		let supported = self.supported.unwrap_or_else(|| c.atoms());
+
+		set_supporting_wm_check(c, window, window);
+		set_wm_name(c, window, &self.name);
+		set_supporting_wm_check(c, root, window);
+		set_supported(c, screen, &supported);
+		set_number_of_desktops(c, screen, desktops);
+		set_desktop_names(c, screen, self.names.iter().map(|n| n.as_ref()));
+		set_current_desktop_checked(c, screen, self.current).request_check()?;
+
+		Ok(announcement)
+	}
+}
+
 define!(cookie GetVirtualRootsCookie through Connection with xcb_ewmh_get_virtual_roots_reply => GetVirtualRootsReply);
 define!(reply GetVirtualRootsReply for xcb_ewmh_get_windows_reply_t with xcb_ewmh_get_windows_reply_wipe);
 
-pub fn set_virtual_roots<'a>(c: &'a Connection, screen: i32, list: &[xcb::Window]) -> xcb::VoidCookie<'a> {
+pub fn set_virtual_roots<'a>(c: &'a Connection, screen: Screen, list: &[xcb::Window]) -> xcb::VoidCookie<'a> {
 	void!(unchecked -> c,
-		xcb_ewmh_set_virtual_roots(c.get_raw_conn(), screen as c_int, list.len() as u32, list.as_ptr()))
+		xcb_ewmh_set_virtual_roots(c.get_raw_conn(), screen.index() as c_int, list.len() as u32, list.as_ptr()))
 }
 
-pub fn set_virtual_roots_checked<'a>(c: &'a Connection, screen: i32, list: &[xcb::Window]) -> xcb::VoidCookie<'a> {
+pub fn set_virtual_roots_checked<'a>(c: &'a Connection, screen: Screen, list: &[xcb::Window]) -> xcb::VoidCookie<'a> {
 	void!(checked -> c,
-		xcb_ewmh_set_virtual_roots_checked(c.get_raw_conn(), screen as c_int, list.len() as u32, list.as_ptr()))
+		xcb_ewmh_set_virtual_roots_checked(c.get_raw_conn(), screen.index() as c_int, list.len() as u32, list.as_ptr()))
 }
 
-pub fn get_virtual_roots(c: &Connection, screen: i32) -> GetVirtualRootsCookie {
+pub fn get_virtual_roots(c: &Connection, screen: Screen) -> GetVirtualRootsCookie {
 	property!(checked GetVirtualRootsCookie -> c,
-		xcb_ewmh_get_virtual_roots(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_virtual_roots(c.get_raw_conn(), screen.index() as c_int))
 }
 
-pub fn get_virtual_roots_unchecked(c: &Connection, screen: i32) -> GetVirtualRootsCookie {
+pub fn get_virtual_roots_unchecked(c: &Connection, screen: Screen) -> GetVirtualRootsCookie {
 	property!(unchecked GetVirtualRootsCookie -> c,
-		xcb_ewmh_get_virtual_roots_unchecked(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_virtual_roots_unchecked(c.get_raw_conn(), screen.index() as c_int))
 }
 
 define!(cookie GetDesktopLayoutCookie through Connection with xcb_ewmh_get_desktop_layout_reply => GetDesktopLayoutReply);
@@ -1081,46 +2296,46 @@ impl GetDesktopLayoutReply {
 	}
 }
 
-pub fn set_desktop_layout(c: &Connection, screen: i32, orientation: DesktopLayoutOrientation, columns: u32, rows: u32, starting_corner: DesktopLayoutStartingCorner) -> xcb::VoidCookie {
+pub fn set_desktop_layout(c: &Connection, screen: Screen, orientation: DesktopLayoutOrientation, columns: u32, rows: u32, starting_corner: DesktopLayoutStartingCorner) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_set_desktop_layout(c.get_raw_conn(), screen as c_int, orientation, columns, rows, starting_corner))
+		xcb_ewmh_set_desktop_layout(c.get_raw_conn(), screen.index() as c_int, orientation, columns, rows, starting_corner))
 }
 
-pub fn set_desktop_layout_checked(c: &Connection, screen: i32, orientation: DesktopLayoutOrientation, columns: u32, rows: u32, starting_corner: DesktopLayoutStartingCorner) -> xcb::VoidCookie {
+pub fn set_desktop_layout_checked(c: &Connection, screen: Screen, orientation: DesktopLayoutOrientation, columns: u32, rows: u32, starting_corner: DesktopLayoutStartingCorner) -> xcb::VoidCookie {
 	void!(checked -> c,
-		xcb_ewmh_set_desktop_layout_checked(c.get_raw_conn(), screen as c_int, orientation, columns, rows, starting_corner))
+		xcb_ewmh_set_desktop_layout_checked(c.get_raw_conn(), screen.index() as c_int, orientation, columns, rows, starting_corner))
 }
 
-pub fn get_desktop_layout(c: &Connection, screen: i32) -> GetDesktopLayoutCookie {
+pub fn get_desktop_layout(c: &Connection, screen: Screen) -> GetDesktopLayoutCookie {
 	property!(checked GetDesktopLayoutCookie -> c,
-		xcb_ewmh_get_desktop_layout(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_desktop_layout(c.get_raw_conn(), screen.index() as c_int))
 }
 
-pub fn get_desktop_layout_unchecked(c: &Connection, screen: i32) -> GetDesktopLayoutCookie {
+pub fn get_desktop_layout_unchecked(c: &Connection, screen: Screen) -> GetDesktopLayoutCookie {
 	property!(unchecked GetDesktopLayoutCookie -> c,
-		xcb_ewmh_get_desktop_layout_unchecked(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_desktop_layout_unchecked(c.get_raw_conn(), screen.index() as c_int))
 }
 
 define!(cookie GetShowingDesktopCookie through Connection with xcb_ewmh_get_showing_desktop_reply as u32);
 
-pub fn set_showing_desktop(c: &Connection, screen: i32, desktop: u32) -> xcb::VoidCookie {
+pub fn set_showing_desktop(c: &Connection, screen: Screen, desktop: u32) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_set_showing_desktop(c.get_raw_conn(), screen as c_int, desktop))
+		xcb_ewmh_set_showing_desktop(c.get_raw_conn(), screen.index() as c_int, desktop))
 }
 
-pub fn set_showing_desktop_checked(c: &Connection, screen: i32, desktop: u32) -> xcb::VoidCookie {
+pub fn set_showing_desktop_checked(c: &Connection, screen: Screen, desktop: u32) -> xcb::VoidCookie {
 	void!(checked -> c,
-		xcb_ewmh_set_showing_desktop_checked(c.get_raw_conn(), screen as c_int, desktop))
+		xcb_ewmh_set_showing_desktop_checked(c.get_raw_conn(), screen.index() as c_int, desktop))
 }
 
-pub fn get_showing_desktop(c: &Connection, screen: i32) -> GetShowingDesktopCookie {
+pub fn get_showing_desktop(c: &Connection, screen: Screen) -> GetShowingDesktopCookie {
 	property!(checked GetShowingDesktopCookie -> c,
-		xcb_ewmh_get_showing_desktop(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_showing_desktop(c.get_raw_conn(), screen.index() as c_int))
 }
 
-pub fn get_showing_desktop_unchecked(c: &Connection, screen: i32) -> GetShowingDesktopCookie {
+pub fn get_showing_desktop_unchecked(c: &Connection, screen: Screen) -> GetShowingDesktopCookie {
 	property!(unchecked GetShowingDesktopCookie -> c,
-		xcb_ewmh_get_showing_desktop_unchecked(c.get_raw_conn(), screen as c_int))
+		xcb_ewmh_get_showing_desktop_unchecked(c.get_raw_conn(), screen.index() as c_int))
 }
 
 define!(cookie GetWmNameCookie through Connection with xcb_ewmh_get_wm_name_reply => GetWmNameReply);
@@ -1186,6 +2401,12 @@ pub fn get_wm_visible_name_unchecked(c: &Connection, window: xcb::Window) -> Get
 define!(cookie GetWmIconNameCookie through Connection with xcb_ewmh_get_wm_icon_name_reply => GetWmIconNameReply);
 define!(reply GetWmIconNameReply for xcb_ewmh_get_utf8_strings_reply_t with xcb_ewmh_get_utf8_strings_reply_wipe);
 
+impl GetWmIconNameReply {
+	pub fn string(&self) -> &str {
+		utf8::into(self.0.strings, self.0.strings_len).get(0).unwrap_or(&"")
+	}
+}
+
 pub fn set_wm_icon_name<T: AsRef<str>>(c: &Connection, window: xcb::Window, name: T) -> xcb::VoidCookie {
 	let value = utf8::from(vec![name.as_ref()]);
 
@@ -1249,9 +2470,9 @@ pub fn set_wm_desktop_checked(c: &Connection, window: xcb::Window, number: u32)
 		xcb_ewmh_set_wm_desktop_checked(c.get_raw_conn(), window, number))
 }
 
-pub fn request_change_wm_desktop(c: &Connection, screen: i32, window: xcb::Window, desktop: u32, source_indication: ClientSourceType) -> xcb::VoidCookie {
+pub fn request_change_wm_desktop(c: &Connection, screen: Screen, window: xcb::Window, desktop: u32, source_indication: ClientSourceType) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_request_change_wm_desktop(c.get_raw_conn(), screen as c_int, window,
+		xcb_ewmh_request_change_wm_desktop(c.get_raw_conn(), screen.index() as c_int, window,
 			desktop, source_indication))
 }
 
@@ -1317,9 +2538,9 @@ pub fn set_wm_state_checked<'a>(c: &'a Connection, window: xcb::Window, list: &[
 		xcb_ewmh_set_wm_state_checked(c.get_raw_conn(), window, list.len() as u32, list.as_ptr()))
 }
 
-pub fn request_change_wm_state(c: &Connection, screen: i32, window: xcb::Window, action: StateAction, first: xcb::Atom, second: xcb::Atom, source_indication: ClientSourceType) -> xcb::VoidCookie {
+pub fn request_change_wm_state(c: &Connection, screen: Screen, window: xcb::Window, action: StateAction, first: xcb::Atom, second: xcb::Atom, source_indication: ClientSourceType) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_request_change_wm_state(c.get_raw_conn(), screen as c_int, window, action, first, second, source_indication))
+		xcb_ewmh_request_change_wm_state(c.get_raw_conn(), screen.index() as c_int, window, action, first, second, source_indication))
 }
 
 pub fn get_wm_state(c: &Connection, window: xcb::Window) -> GetWmStateCookie {
@@ -1382,7 +2603,7 @@ pub fn get_wm_strut(c: &Connection, window: xcb::Window) -> GetWmStrutCookie {
 
 pub fn get_wm_strut_unchecked(c: &Connection, window: xcb::Window) -> GetWmStrutCookie {
 	property!(unchecked GetWmStrutCookie -> c,
-		xcb_ewmh_get_wm_strut(c.get_raw_conn(), window))
+		xcb_ewmh_get_wm_strut_unchecked(c.get_raw_conn(), window))
 }
 
 define!(cookie GetWmStrutPartialCookie through Connection with xcb_ewmh_get_wm_strut_partial_reply as StrutPartial);
@@ -1404,7 +2625,7 @@ pub fn get_wm_strut_partial(c: &Connection, window: xcb::Window) -> GetWmStrutPa
 
 pub fn get_wm_strut_partial_unchecked(c: &Connection, window: xcb::Window) -> GetWmStrutPartialCookie {
 	property!(unchecked GetWmStrutPartialCookie -> c,
-		xcb_ewmh_get_wm_strut_partial(c.get_raw_conn(), window))
+		xcb_ewmh_get_wm_strut_partial_unchecked(c.get_raw_conn(), window))
 }
 
 define!(cookie GetWmIconGeometryCookie through Connection with xcb_ewmh_get_wm_icon_geometry_reply as Geometry);
@@ -1440,10 +2661,41 @@ impl GetWmIconReply {
 	}
 
 	pub fn icons(&self) -> WmIconIterator {
-		unsafe {
-			xcb_ewmh_get_wm_icon_iterator(&self.0)
+		WmIconIterator {
+			iter:   unsafe { xcb_ewmh_get_wm_icon_iterator(&self.0) },
+			marker: PhantomData,
 		}
 	}
+
+	/// Picks the icon best suited to be shown at the given size, the smallest
+	/// one at least as big or the biggest one otherwise.
+	pub fn best_for(&self, size: u32) -> Option<WmIcon> {
+		self.icons().min_by_key(|icon| {
+			let side = cmp::max(icon.width(), icon.height());
+
+			if side >= size {
+				(0, side)
+			}
+			else {
+				(1, u32::MAX - side)
+			}
+		})
+	}
+
+	/// Picks the best icon for the size and resizes it to fit in a square of
+	/// that size, returning the ARGB32 pixels and the resulting size.
+	pub fn scaled_for(&self, size: u32, filter: IconFilter) -> Option<(u32, u32, Vec<u32>)> {
+		let icon = self.best_for(size)?;
+
+		if icon.width() == 0 || icon.height() == 0 {
+			return None;
+		}
+
+		// Keep the aspect ratio, the larger side becomes `size`.
+		let (width, height) = fit(icon.width(), icon.height(), size);
+
+		Some((width, height, icon.resize(width, height, filter)))
+	}
 }
 
 pub fn set_wm_icon<'a>(c: &'a Connection, mode: u8, window: xcb::Window, data: &[u32]) -> xcb::VoidCookie<'a> {
@@ -1476,6 +2728,194 @@ pub fn get_wm_icon_unchecked(c: &Connection, window: xcb::Window) -> GetWmIconCo
 		xcb_ewmh_get_wm_icon_unchecked(c.get_raw_conn(), window))
 }
 
+/// Errors from building a `_NET_WM_ICON` value.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IconError {
+	/// The icon has a zero width or height.
+	Empty,
+
+	/// The buffer does not hold exactly `width * height` pixels.
+	Length {
+		expected: usize,
+		actual:   usize,
+	},
+
+	/// The RGBA buffer of `length` bytes doesn't hold whole pixels.
+	Unaligned {
+		length: usize,
+	},
+
+	/// The icons don't fit in a single request, both lengths are in 4-byte
+	/// units.
+	TooLarge {
+		length:  usize,
+		maximum: usize,
+	},
+}
+
+impl fmt::Display for IconError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			IconError::Empty =>
+				f.write_str("empty icon"),
+
+			IconError::Length { expected, actual } =>
+				write!(f, "expected {} pixels, got {}", expected, actual),
+
+			IconError::Unaligned { length } =>
+				write!(f, "{} bytes are not whole RGBA pixels", length),
+
+			IconError::TooLarge { length, maximum } =>
+				write!(f, "icons need a request of {} units, the server accepts {}", length, maximum),
+		}
+	}
+}
+
+impl error::Error for IconError { }
+
+/// The standard icon sizes generated by `WmIconsBuilder::standard_sizes`.
+pub const STANDARD_ICON_SIZES: [u32; 3] = [16, 32, 48];
+
+/// An encoded `_NET_WM_ICON` value holding one or more icons.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct WmIcons(Vec<u32>);
+
+impl WmIcons {
+	pub fn empty() -> WmIconsBuilder {
+		WmIconsBuilder { icons: Vec::new(), standard: false }
+	}
+
+	/// The CARDINAL array as sent over the wire.
+	pub fn data(&self) -> &[u32] {
+		&self.0
+	}
+}
+
+pub struct WmIconsBuilder {
+	icons:    Vec<(u32, u32, Vec<u32>)>,
+	standard: bool,
+}
+
+impl WmIconsBuilder {
+	/// Adds an icon from ARGB32 pixels with straight alpha.
+	pub fn argb(mut self, width: u32, height: u32, pixels: &[u32]) -> Result<Self, IconError> {
+		check(width, height, pixels.len())?;
+		self.icons.push((width, height, pixels.to_vec()));
+
+		Ok(self)
+	}
+
+	/// Adds an icon from RGBA bytes with straight alpha.
+	pub fn rgba(mut self, width: u32, height: u32, pixels: &[u8]) -> Result<Self, IconError> {
+		let chunks = pixels.chunks_exact(4);
+
+		if !chunks.remainder().is_empty() {
+			return Err(IconError::Unaligned { length: pixels.len() });
+		}
+
+		check(width, height, chunks.len())?;
+		self.icons.push((width, height, chunks.map(|p|
+			(p[3] as u32) << 24 | (p[0] as u32) << 16 | (p[1] as u32) << 8 | p[2] as u32).collect()));
+
+		Ok(self)
+	}
+
+	/// Generates any missing 16, 32 and 48 pixel icons from the largest icon
+	/// added, keeping its aspect ratio so the size is the longest side.
+	pub fn standard_sizes(mut self) -> Self {
+		self.standard = true;
+		self
+	}
+
+	pub fn build(mut self) -> WmIcons {
+		if self.standard {
+			let generated = self.icons.iter()
+				.max_by_key(|&&(width, height, _)| width as u64 * height as u64)
+				.map(|&(width, height, ref data)| {
+					let source = WmIcon { width: width, height: height, data: data };
+
+					STANDARD_ICON_SIZES.iter()
+						.filter(|&&size| !self.icons.iter().any(|&(w, h, _)| cmp::max(w, h) == size))
+						.map(|&size| {
+							let (w, h) = fit(width, height, size);
+							(w, h, source.resize(w, h, IconFilter::Box))
+						})
+						.collect::<Vec<_>>()
+				})
+				.unwrap_or_default();
+
+			self.icons.extend(generated);
+		}
+
+		let mut data = Vec::with_capacity(self.icons.iter().map(|i| 2 + i.2.len()).sum());
+
+		for (width, height, pixels) in self.icons {
+			data.push(width);
+			data.push(height);
+			data.extend(pixels);
+		}
+
+		WmIcons(data)
+	}
+}
+
+// Scales the size so the longest side becomes `size`, rounding the other.
+fn fit(width: u32, height: u32, size: u32) -> (u32, u32) {
+	let scale = |side: u32, longest: u32|
+		cmp::max(1, ((side as u64 * size as u64 + longest as u64 / 2) / longest as u64) as u32);
+
+	if width >= height {
+		(size, scale(height, width))
+	}
+	else {
+		(scale(width, height), size)
+	}
+}
+
+fn check(width: u32, height: u32, len: usize) -> Result<(), IconError> {
+	if width == 0 || height == 0 {
+		return Err(IconError::Empty);
+	}
+
+	let expected = width as usize * height as usize;
+
+	if len != expected {
+		return Err(IconError::Length { expected: expected, actual: len });
+	}
+
+	Ok(())
+}
+
+/// Replaces the `_NET_WM_ICON` of the window, failing if the icons exceed
+/// the maximum request length of the server.
+pub fn set_wm_icons<'a>(c: &'a Connection, window: xcb::Window, icons: &WmIcons) -> Result<xcb::VoidCookie<'a>, IconError> {
+	icons_fit(c, icons)?;
+	Ok(set_wm_icon(c, xcb::PROP_MODE_REPLACE as u8, window, icons.data()))
+}
+
+pub fn set_wm_icons_checked<'a>(c: &'a Connection, window: xcb::Window, icons: &WmIcons) -> Result<xcb::VoidCookie<'a>, IconError> {
+	icons_fit(c, icons)?;
+	Ok(set_wm_icon_checked(c, xcb::PROP_MODE_REPLACE as u8, window, icons.data()))
+}
+
+fn icons_fit(c: &Connection, icons: &WmIcons) -> Result<(), IconError> {
+	// The `ChangeProperty` header takes 6 units, and one more for the
+	// BIG-REQUESTS length once the total doesn't fit in 16 bits.
+	let mut length = 6 + icons.data().len();
+
+	if length > u16::MAX as usize {
+		length += 1;
+	}
+
+	let maximum = c.get_maximum_request_length() as usize;
+
+	if length > maximum {
+		return Err(IconError::TooLarge { length: length, maximum: maximum });
+	}
+
+	Ok(())
+}
+
 define!(cookie GetWmPidCookie through Connection with xcb_ewmh_get_wm_pid_reply as u32);
 
 pub fn set_wm_pid(c: &Connection, window: xcb::Window, pid: u32) -> xcb::VoidCookie {
@@ -1494,7 +2934,7 @@ pub fn get_wm_pid(c: &Connection, window: xcb::Window) -> GetWmPidCookie {
 }
 
 pub fn get_wm_pid_unchecked(c: &Connection, window: xcb::Window) -> GetWmPidCookie {
-	property!(checked GetWmPidCookie -> c,
+	property!(unchecked GetWmPidCookie -> c,
 		xcb_ewmh_get_wm_pid_unchecked(c.get_raw_conn(), window))
 }
 
@@ -1516,7 +2956,7 @@ pub fn get_wm_handled_icons(c: &Connection, window: xcb::Window) -> GetWmHandled
 }
 
 pub fn get_wm_handled_icons_unchecked(c: &Connection, window: xcb::Window) -> GetWmHandledIconsCookie {
-	property!(checked GetWmHandledIconsCookie -> c,
+	property!(unchecked GetWmHandledIconsCookie -> c,
 		xcb_ewmh_get_wm_handled_icons_unchecked(c.get_raw_conn(), window))
 }
 
@@ -1538,7 +2978,7 @@ pub fn get_wm_user_time(c: &Connection, window: xcb::Window) -> GetWmUserTimeCoo
 }
 
 pub fn get_wm_user_time_unchecked(c: &Connection, window: xcb::Window) -> GetWmUserTimeCookie {
-	property!(checked GetWmUserTimeCookie -> c,
+	property!(unchecked GetWmUserTimeCookie -> c,
 		xcb_ewmh_get_wm_user_time_unchecked(c.get_raw_conn(), window))
 }
 
@@ -1560,7 +3000,7 @@ pub fn get_wm_user_time_window(c: &Connection, window: xcb::Window) -> GetWmUser
 }
 
 pub fn get_wm_user_time_window_unchecked(c: &Connection, window: xcb::Window) -> GetWmUserTimeWindowCookie {
-	property!(checked GetWmUserTimeWindowCookie -> c,
+	property!(unchecked GetWmUserTimeWindowCookie -> c,
 		xcb_ewmh_get_wm_user_time_window_unchecked(c.get_raw_conn(), window))
 }
 
@@ -1600,7 +3040,7 @@ pub fn set_wm_sync_request_counter_checked(c: &Connection, window: xcb::Window,
 
 pub fn send_wm_sync_request(c: &Connection, window: xcb::Window, wm_protocols: xcb::Atom, wm_sync_request: xcb::Atom, timestamp: xcb::Timestamp, counter: u64) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_send_wm_sync_rqeuest(c.get_raw_conn(), window, wm_protocols, wm_sync_request, timestamp, counter))
+		xcb_ewmh_send_wm_sync_rqeuest(c.get_raw_conn(), window, wm_protocols, wm_sync_request, c.time(timestamp), counter))
 }
 
 pub fn get_wm_sync_request_counter(c: &Connection, window: xcb::Window) -> GetWmSyncRequestCounterCookie {
@@ -1625,9 +3065,9 @@ pub fn set_wm_full_screen_monitors_checked(c: &Connection, window: xcb::Window,
 		xcb_ewmh_set_wm_fullscreen_monitors_checked(c.get_raw_conn(), window, top, bottom, left, right))
 }
 
-pub fn request_change_wm_full_screen_monitors(c: &Connection, screen: i32, window: xcb::Window, top: u32, bottom: u32, left: u32, right: u32, source_indication: ClientSourceType) -> xcb::VoidCookie {
+pub fn request_change_wm_full_screen_monitors(c: &Connection, screen: Screen, window: xcb::Window, top: u32, bottom: u32, left: u32, right: u32, source_indication: ClientSourceType) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_request_change_wm_fullscreen_monitors(c.get_raw_conn(), screen as c_int, window, top, bottom, left, right, source_indication))
+		xcb_ewmh_request_change_wm_fullscreen_monitors(c.get_raw_conn(), screen.index() as c_int, window, top, bottom, left, right, source_indication))
 }
 
 pub fn get_wm_full_screen_monitors(c: &Connection, window: xcb::Window) -> GetWmFullScreenMonitorsCookie {
@@ -1642,32 +3082,198 @@ pub fn get_wm_full_screen_monitors_unchecked(c: &Connection, window: xcb::Window
 
 define!(cookie GetWmCmOwnerCookie(xcb_get_selection_owner_cookie_t) through Connection with xcb_ewmh_get_wm_cm_owner_reply as xcb::Window);
 
-pub fn set_wm_cm_owner(c: &Connection, screen: i32, owner: xcb::Window, timestamp: xcb::Timestamp, first: u32, second: u32) -> xcb::VoidCookie {
+pub fn set_wm_cm_owner(c: &Connection, screen: Screen, owner: xcb::Window, timestamp: xcb::Timestamp, first: u32, second: u32) -> xcb::VoidCookie {
 	void!(unchecked -> c,
-		xcb_ewmh_set_wm_cm_owner(c.get_raw_conn(), screen as c_int, owner, timestamp, first, second))
+		xcb_ewmh_set_wm_cm_owner(c.get_raw_conn(), screen.index() as c_int, owner, timestamp, first, second))
 }
 
-pub fn set_wm_cm_owner_checked(c: &Connection, screen: i32, owner: xcb::Window, timestamp: xcb::Timestamp, first: u32, second: u32) -> xcb::VoidCookie {
+pub fn set_wm_cm_owner_checked(c: &Connection, screen: Screen, owner: xcb::Window, timestamp: xcb::Timestamp, first: u32, second: u32) -> xcb::VoidCookie {
 	void!(checked -> c,
-		xcb_ewmh_set_wm_cm_owner_checked(c.get_raw_conn(), screen as c_int, owner, timestamp, first, second))
+		xcb_ewmh_set_wm_cm_owner_checked(c.get_raw_conn(), screen.index() as c_int, owner, timestamp, first, second))
 }
 
-pub fn get_wm_cm_owner(c: &Connection, screen: i32) -> GetWmCmOwnerCookie {
+pub fn get_wm_cm_owner(c: &Connection, screen: Screen) -> GetWmCmOwnerCookie {
 	unsafe {
 		GetWmCmOwnerCookie {
 			conn:    c,
-			cookie:  xcb_ewmh_get_wm_cm_owner(c.get_raw_conn(), screen as c_int),
+			cookie:  xcb_ewmh_get_wm_cm_owner(c.get_raw_conn(), screen.index() as c_int),
 			checked: true,
+			taken:   AtomicBool::new(false),
 		}
 	}
 }
 
-pub fn get_wm_cm_owner_unchecked(c: &Connection, screen: i32) -> GetWmCmOwnerCookie {
+pub fn get_wm_cm_owner_unchecked(c: &Connection, screen: Screen) -> GetWmCmOwnerCookie {
 	unsafe {
 		GetWmCmOwnerCookie {
 			conn:    c,
-			cookie:  xcb_ewmh_get_wm_cm_owner_unchecked(c.get_raw_conn(), screen as c_int),
+			cookie:  xcb_ewmh_get_wm_cm_owner_unchecked(c.get_raw_conn(), screen.index() as c_int),
 			checked: false,
+			taken:   AtomicBool::new(false),
 		}
 	}
 }
+
+// The extension properties aren't known to libxcb-ewmh, so their cookies are
+// made here and decoded with the generic reply functions where possible.
+unsafe fn get_cardinals(c: &Connection, checked: bool, window: xcb::Window, property: xcb::Atom, length: u32) -> xcb_get_property_cookie_t {
+	if checked {
+		xcb_get_property(c.ewmh.connection, 0, window, property, xcb::ATOM_CARDINAL, 0, length)
+	}
+	else {
+		xcb_get_property_unchecked(c.ewmh.connection, 0, window, property, xcb::ATOM_CARDINAL, 0, length)
+	}
+}
+
+fn set_cardinals<'a>(c: &'a Connection, checked: bool, window: xcb::Window, property: xcb::Atom, data: &[u32]) -> xcb::VoidCookie<'a> {
+	if checked {
+		xcb::change_property_checked(c, xcb::PROP_MODE_REPLACE as u8, window, property, xcb::ATOM_CARDINAL, 32, data)
+	}
+	else {
+		xcb::change_property(c, xcb::PROP_MODE_REPLACE as u8, window, property, xcb::ATOM_CARDINAL, 32, data)
+	}
+}
+
+unsafe fn get_wm_window_opacity_reply(ewmh: *mut xcb_ewmh_connection_t, cookie: xcb_get_property_cookie_t, opacity: *mut f32, e: *mut *mut xcb_generic_error_t) -> u8 {
+	let mut value = 0;
+	let     res   = xcb_ewmh_get_cardinal_reply(ewmh, cookie, &mut value, e);
+
+	*opacity = (value as f64 / u32::MAX as f64) as f32;
+	res
+}
+
+define!(cookie GetWmWindowOpacityCookie through Connection with get_wm_window_opacity_reply as f32);
+
+/// Sets `_NET_WM_WINDOW_OPACITY`, clamped between 0.0 and 1.0.
+pub fn set_wm_window_opacity(c: &Connection, window: xcb::Window, opacity: f32) -> xcb::VoidCookie {
+	let value = (opacity.clamp(0.0, 1.0) as f64 * u32::MAX as f64).round() as u32;
+	set_cardinals(c, false, window, c.WM_WINDOW_OPACITY(), &[value])
+}
+
+pub fn set_wm_window_opacity_checked(c: &Connection, window: xcb::Window, opacity: f32) -> xcb::VoidCookie {
+	let value = (opacity.clamp(0.0, 1.0) as f64 * u32::MAX as f64).round() as u32;
+	set_cardinals(c, true, window, c.WM_WINDOW_OPACITY(), &[value])
+}
+
+pub fn get_wm_window_opacity(c: &Connection, window: xcb::Window) -> GetWmWindowOpacityCookie {
+	property!(checked GetWmWindowOpacityCookie -> c,
+		get_cardinals(c, true, window, c.WM_WINDOW_OPACITY(), 1))
+}
+
+pub fn get_wm_window_opacity_unchecked(c: &Connection, window: xcb::Window) -> GetWmWindowOpacityCookie {
+	property!(unchecked GetWmWindowOpacityCookie -> c,
+		get_cardinals(c, false, window, c.WM_WINDOW_OPACITY(), 1))
+}
+
+struct CardinalsReply {
+	reply: *mut xcb_get_property_reply_t,
+}
+
+unsafe fn cardinals_reply_wipe(data: *mut CardinalsReply) {
+	libc::free((*data).reply as *mut _);
+}
+
+unsafe fn get_cardinals_reply(ewmh: *mut xcb_ewmh_connection_t, cookie: xcb_get_property_cookie_t, cardinals: *mut CardinalsReply, e: *mut *mut xcb_generic_error_t) -> u8 {
+	let reply = xcb_get_property_reply((*ewmh).connection, cookie, e);
+
+	if reply.is_null() {
+		return 0;
+	}
+
+	if (*reply).type_ != xcb::ATOM_CARDINAL || (*reply).format != 32 {
+		libc::free(reply as *mut _);
+		return 0;
+	}
+
+	(*cardinals).reply = reply;
+	1
+}
+
+define!(cookie GetWmOpaqueRegionCookie through Connection with get_cardinals_reply => GetWmOpaqueRegionReply);
+define!(reply GetWmOpaqueRegionReply for CardinalsReply with cardinals_reply_wipe);
+
+impl GetWmOpaqueRegionReply {
+	pub fn rectangles(&self) -> Vec<xcb::Rectangle> {
+		let data = unsafe {
+			slice::from_raw_parts(xcb_get_property_value(self.0.reply) as *const u32,
+				xcb_get_property_value_length(self.0.reply) as usize / 4)
+		};
+
+		data.chunks_exact(4).map(|rect|
+			xcb::Rectangle::new(rect[0] as i16, rect[1] as i16, rect[2] as u16, rect[3] as u16)).collect()
+	}
+}
+
+fn opaque_region(rectangles: &[xcb::Rectangle]) -> Vec<u32> {
+	rectangles.iter().flat_map(|rect|
+		vec![rect.x() as u32, rect.y() as u32, rect.width() as u32, rect.height() as u32]).collect()
+}
+
+pub fn set_wm_opaque_region<'a>(c: &'a Connection, window: xcb::Window, rectangles: &[xcb::Rectangle]) -> xcb::VoidCookie<'a> {
+	set_cardinals(c, false, window, c.WM_OPAQUE_REGION(), &opaque_region(rectangles))
+}
+
+pub fn set_wm_opaque_region_checked<'a>(c: &'a Connection, window: xcb::Window, rectangles: &[xcb::Rectangle]) -> xcb::VoidCookie<'a> {
+	set_cardinals(c, true, window, c.WM_OPAQUE_REGION(), &opaque_region(rectangles))
+}
+
+pub fn get_wm_opaque_region(c: &Connection, window: xcb::Window) -> GetWmOpaqueRegionCookie {
+	property!(checked GetWmOpaqueRegionCookie -> c,
+		get_cardinals(c, true, window, c.WM_OPAQUE_REGION(), u32::MAX))
+}
+
+pub fn get_wm_opaque_region_unchecked(c: &Connection, window: xcb::Window) -> GetWmOpaqueRegionCookie {
+	property!(unchecked GetWmOpaqueRegionCookie -> c,
+		get_cardinals(c, false, window, c.WM_OPAQUE_REGION(), u32::MAX))
+}
+
+pub type BypassCompositor = u32;
+pub const BYPASS_COMPOSITOR_NO_PREFERENCE: BypassCompositor = 0;
+pub const BYPASS_COMPOSITOR_DISABLE:       BypassCompositor = 1;
+pub const BYPASS_COMPOSITOR_ENABLE:        BypassCompositor = 2;
+
+define!(cookie GetWmBypassCompositorCookie through Connection with xcb_ewmh_get_cardinal_reply as BypassCompositor);
+
+pub fn set_wm_bypass_compositor(c: &Connection, window: xcb::Window, value: BypassCompositor) -> xcb::VoidCookie {
+	set_cardinals(c, false, window, c.WM_BYPASS_COMPOSITOR(), &[value])
+}
+
+pub fn set_wm_bypass_compositor_checked(c: &Connection, window: xcb::Window, value: BypassCompositor) -> xcb::VoidCookie {
+	set_cardinals(c, true, window, c.WM_BYPASS_COMPOSITOR(), &[value])
+}
+
+pub fn get_wm_bypass_compositor(c: &Connection, window: xcb::Window) -> GetWmBypassCompositorCookie {
+	property!(checked GetWmBypassCompositorCookie -> c,
+		get_cardinals(c, true, window, c.WM_BYPASS_COMPOSITOR(), 1))
+}
+
+pub fn get_wm_bypass_compositor_unchecked(c: &Connection, window: xcb::Window) -> GetWmBypassCompositorCookie {
+	property!(unchecked GetWmBypassCompositorCookie -> c,
+		get_cardinals(c, false, window, c.WM_BYPASS_COMPOSITOR(), 1))
+}
+
+define!(cookie GetGtkFrameExtentsCookie through Connection with xcb_ewmh_get_frame_extents_reply as Extents);
+
+pub fn set_gtk_frame_extents(c: &Connection, window: xcb::Window, left: u32, right: u32, top: u32, bottom: u32) -> xcb::VoidCookie {
+	set_cardinals(c, false, window, c.GTK_FRAME_EXTENTS(), &[left, right, top, bottom])
+}
+
+pub fn set_gtk_frame_extents_checked(c: &Connection, window: xcb::Window, left: u32, right: u32, top: u32, bottom: u32) -> xcb::VoidCookie {
+	set_cardinals(c, true, window, c.GTK_FRAME_EXTENTS(), &[left, right, top, bottom])
+}
+
+pub fn get_gtk_frame_extents(c: &Connection, window: xcb::Window) -> GetGtkFrameExtentsCookie {
+	property!(checked GetGtkFrameExtentsCookie -> c,
+		get_cardinals(c, true, window, c.GTK_FRAME_EXTENTS(), 4))
+}
+
+pub fn get_gtk_frame_extents_unchecked(c: &Connection, window: xcb::Window) -> GetGtkFrameExtentsCookie {
+	property!(unchecked GetGtkFrameExtentsCookie -> c,
+		get_cardinals(c, false, window, c.GTK_FRAME_EXTENTS(), 4))
+}
+
+/// Asks the window manager to show the window menu at the given position,
+/// as sent by GTK for client-side decorations.
+pub fn request_show_window_menu(c: &Connection, screen: Screen, window: xcb::Window, device: u32, x_root: i32, y_root: i32) -> xcb::VoidCookie {
+	send_client_message(c, window, screen.root(), c.GTK_SHOW_WINDOW_MENU(), &[device, x_root as u32, y_root as u32])
+}
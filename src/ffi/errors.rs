@@ -0,0 +1,18 @@
+use xcb::ffi::*;
+use libc::{c_int, c_char};
+
+pub enum xcb_errors_context_t {}
+
+#[cfg_attr(feature = "static", link(name = "xcb-errors", kind = "static"))]
+#[cfg_attr(not(feature = "static"), link(name = "xcb-errors"))]
+extern "C" {
+	pub fn xcb_errors_context_new(conn: *mut xcb_connection_t, ctx: *mut *mut xcb_errors_context_t) -> c_int;
+	pub fn xcb_errors_context_free(ctx: *mut xcb_errors_context_t);
+
+	pub fn xcb_errors_get_name_for_major_code(ctx: *mut xcb_errors_context_t, major_code: u8) -> *const c_char;
+	pub fn xcb_errors_get_name_for_minor_code(ctx: *mut xcb_errors_context_t, major_code: u8, minor_code: u16) -> *const c_char;
+	pub fn xcb_errors_get_name_for_core_event(ctx: *mut xcb_errors_context_t, event_code: u8, extension: *mut *const c_char) -> *const c_char;
+	pub fn xcb_errors_get_name_for_xge_event(ctx: *mut xcb_errors_context_t, major_code: u8, event_type: u16) -> *const c_char;
+	pub fn xcb_errors_get_name_for_xcb_event(ctx: *mut xcb_errors_context_t, event: *mut xcb_generic_event_t, extension: *mut *const c_char) -> *const c_char;
+	pub fn xcb_errors_get_name_for_error(ctx: *mut xcb_errors_context_t, error_code: u8, extension: *mut *const c_char) -> *const c_char;
+}
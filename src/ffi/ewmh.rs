@@ -120,6 +120,7 @@ pub struct xcb_ewmh_get_utf8_strings_reply_t {
 	_reply: *mut xcb_get_property_reply_t,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(C)]
 pub struct xcb_ewmh_coordinates_t {
 	pub x: u32,
@@ -134,6 +135,7 @@ pub struct xcb_ewmh_get_desktop_viewport_reply_t {
 	_reply: *mut xcb_get_property_reply_t,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(C)]
 pub struct xcb_ewmh_geometry_t {
 	pub x: u32,
@@ -198,6 +200,7 @@ pub const XCB_EWMH_WM_STATE_REMOVE: xcb_ewmh_wm_state_action_t = 0;
 pub const XCB_EWMH_WM_STATE_ADD:    xcb_ewmh_wm_state_action_t = 1;
 pub const XCB_EWMH_WM_STATE_TOGGLE: xcb_ewmh_wm_state_action_t = 2;
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(C)]
 pub struct xcb_ewmh_wm_strut_partial_t {
 	pub left: u32,
@@ -230,12 +233,13 @@ pub struct xcb_ewmh_get_wm_icon_reply_t {
 	_reply: *mut xcb_get_property_reply_t,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(C)]
 pub struct xcb_ewmh_get_extents_reply_t {
-	pub top: u32,
-	pub bottom: u32,
 	pub left: u32,
 	pub right: u32,
+	pub top: u32,
+	pub bottom: u32,
 }
 
 #[repr(C)]
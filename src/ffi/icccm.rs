@@ -116,6 +116,7 @@ extern "C" {
 	pub fn xcb_icccm_set_wm_name_checked(c: *mut xcb_connection_t, window: xcb_window_t, encoding: xcb_atom_t, format: u8, name_len: u32, name: *const c_char) -> xcb_void_cookie_t;
 	pub fn xcb_icccm_set_wm_name(c: *mut xcb_connection_t, window: xcb_window_t, encoding: xcb_atom_t, format: u8, name_len: u32, name: *const c_char) -> xcb_void_cookie_t;
 	pub fn xcb_icccm_get_wm_name(c: *mut xcb_connection_t, window: xcb_window_t) -> xcb_get_property_cookie_t;
+	pub fn xcb_icccm_get_wm_name_unchecked(c: *mut xcb_connection_t, window: xcb_window_t) -> xcb_get_property_cookie_t;
 	pub fn xcb_icccm_get_wm_name_reply(c: *mut xcb_connection_t, cookie: xcb_get_property_cookie_t, prop: *mut xcb_icccm_get_text_property_reply_t, e: *mut *mut xcb_generic_error_t) -> u8;
 
 	pub fn xcb_icccm_set_wm_icon_name_checked(c: *mut xcb_connection_t, window: xcb_window_t, encoding: xcb_atom_t, format: u8, name_len: u32, name: *const c_char) -> xcb_void_cookie_t;
@@ -151,7 +152,7 @@ extern "C" {
 	pub fn xcb_icccm_get_wm_transient_for(c: *mut xcb_connection_t, window: xcb_window_t) -> xcb_get_property_cookie_t;
 	pub fn xcb_icccm_get_wm_transient_for_unchecked(c: *mut xcb_connection_t, window: xcb_window_t) -> xcb_get_property_cookie_t;
 	pub fn xcb_icccm_get_wm_transient_for_from_reply(prop: *mut xcb_window_t, reply: *mut xcb_get_property_reply_t) -> u8;
-	pub fn xcb_icccm_get_wm_transient_for_reply(c: *mut xcb_connection_t, cookie: xcb_get_property_reply_t, prop: *mut xcb_window_t, e: *mut *mut xcb_generic_error_t) -> u8;
+	pub fn xcb_icccm_get_wm_transient_for_reply(c: *mut xcb_connection_t, cookie: xcb_get_property_cookie_t, prop: *mut xcb_window_t, e: *mut *mut xcb_generic_error_t) -> u8;
 
 	pub fn xcb_icccm_size_hints_set_position(hints: *mut xcb_size_hints_t, user_specified: c_int, x: i32, y: i32);
 	pub fn xcb_icccm_size_hints_set_size(hints: *mut xcb_size_hints_t, user_specified: c_int, width: i32, height: i32);
@@ -12,3 +12,9 @@ pub mod keysyms;
 
 #[cfg(feature = "render")]
 pub mod render;
+
+#[cfg(feature = "async")]
+pub mod poll;
+
+#[cfg(feature = "errors")]
+pub mod errors;
@@ -0,0 +1,9 @@
+use xcb::ffi::*;
+use libc::{c_int, c_uint, c_void};
+
+// Missing from the `xcb` bindings.
+#[cfg_attr(feature = "static", link(name = "xcb", kind = "static"))]
+#[cfg_attr(not(feature = "static"), link(name = "xcb"))]
+extern "C" {
+	pub fn xcb_poll_for_reply(c: *mut xcb_connection_t, request: c_uint, reply: *mut *mut c_void, error: *mut *mut xcb_generic_error_t) -> c_int;
+}
@@ -99,6 +99,11 @@ pub fn get_wm_name(c: &xcb::Connection, window: xcb::Window) -> GetTextPropertyC
 		xcb_icccm_get_wm_name(c.get_raw_conn(), window))
 }
 
+pub fn get_wm_name_unchecked(c: &xcb::Connection, window: xcb::Window) -> GetTextPropertyCookie {
+	property!(unchecked GetTextPropertyCookie with xcb_icccm_get_wm_name_reply -> c,
+		xcb_icccm_get_wm_name_unchecked(c.get_raw_conn(), window))
+}
+
 pub fn set_wm_icon_name<T: AsRef<str>>(c: &xcb::Connection, window: xcb::Window, encoding: xcb::Atom, format: u8, name: T) -> xcb::VoidCookie {
 	let name = name.as_ref();
 
@@ -227,6 +232,28 @@ pub fn get_wm_class_unchecked(c: &xcb::Connection, window: xcb::Window) -> GetWm
 		xcb_icccm_get_wm_class_unchecked(c.get_raw_conn(), window))
 }
 
+define!(cookie GetWmTransientForCookie with xcb_icccm_get_wm_transient_for_reply as xcb::Window);
+
+pub fn set_wm_transient_for(c: &xcb::Connection, window: xcb::Window, transient_for: xcb::Window) -> xcb::VoidCookie {
+	void!(unchecked -> c,
+		xcb_icccm_set_wm_transient_for(c.get_raw_conn(), window, transient_for))
+}
+
+pub fn set_wm_transient_for_checked(c: &xcb::Connection, window: xcb::Window, transient_for: xcb::Window) -> xcb::VoidCookie {
+	void!(checked -> c,
+		xcb_icccm_set_wm_transient_for_checked(c.get_raw_conn(), window, transient_for))
+}
+
+pub fn get_wm_transient_for(c: &xcb::Connection, window: xcb::Window) -> GetWmTransientForCookie {
+	property!(checked GetWmTransientForCookie -> c,
+		xcb_icccm_get_wm_transient_for(c.get_raw_conn(), window))
+}
+
+pub fn get_wm_transient_for_unchecked(c: &xcb::Connection, window: xcb::Window) -> GetWmTransientForCookie {
+	property!(unchecked GetWmTransientForCookie -> c,
+		xcb_icccm_get_wm_transient_for_unchecked(c.get_raw_conn(), window))
+}
+
 pub struct SizeHints(xcb_size_hints_t);
 pub struct SizeHintsBuilder(xcb_size_hints_t);
 
@@ -619,6 +646,232 @@ pub fn get_wm_hints_unchecked(c: &xcb::Connection, window: xcb::Window) -> GetWm
 		xcb_icccm_get_wm_hints_unchecked(c.get_raw_conn(), window))
 }
 
+pub const MOTIF_HINT_FUNCTIONS:   u32 = 1 << 0;
+pub const MOTIF_HINT_DECORATIONS: u32 = 1 << 1;
+pub const MOTIF_HINT_INPUT_MODE:  u32 = 1 << 2;
+pub const MOTIF_HINT_STATUS:      u32 = 1 << 3;
+
+/// When set the other function bits are the ones to remove.
+pub const MOTIF_FUNCTION_ALL:      u32 = 1 << 0;
+pub const MOTIF_FUNCTION_RESIZE:   u32 = 1 << 1;
+pub const MOTIF_FUNCTION_MOVE:     u32 = 1 << 2;
+pub const MOTIF_FUNCTION_MINIMIZE: u32 = 1 << 3;
+pub const MOTIF_FUNCTION_MAXIMIZE: u32 = 1 << 4;
+pub const MOTIF_FUNCTION_CLOSE:    u32 = 1 << 5;
+
+/// When set the other decoration bits are the ones to remove.
+pub const MOTIF_DECORATION_ALL:      u32 = 1 << 0;
+pub const MOTIF_DECORATION_BORDER:   u32 = 1 << 1;
+pub const MOTIF_DECORATION_RESIZEH:  u32 = 1 << 2;
+pub const MOTIF_DECORATION_TITLE:    u32 = 1 << 3;
+pub const MOTIF_DECORATION_MENU:     u32 = 1 << 4;
+pub const MOTIF_DECORATION_MINIMIZE: u32 = 1 << 5;
+pub const MOTIF_DECORATION_MAXIMIZE: u32 = 1 << 6;
+
+pub type MotifInputMode = i32;
+pub const MOTIF_INPUT_MODELESS:                  MotifInputMode = 0;
+pub const MOTIF_INPUT_PRIMARY_APPLICATION_MODAL: MotifInputMode = 1;
+pub const MOTIF_INPUT_SYSTEM_MODAL:              MotifInputMode = 2;
+pub const MOTIF_INPUT_FULL_APPLICATION_MODAL:    MotifInputMode = 3;
+
+pub const MOTIF_STATUS_TEAROFF_WINDOW: u32 = 1 << 0;
+
+/// The `_MOTIF_WM_HINTS` of a window, still used by clients to ask for no
+/// decorations.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MotifHints {
+	flags:       u32,
+	functions:   u32,
+	decorations: u32,
+	input_mode:  MotifInputMode,
+	status:      u32,
+}
+
+pub struct MotifHintsBuilder(MotifHints);
+
+impl MotifHints {
+	pub fn empty() -> MotifHintsBuilder {
+		MotifHintsBuilder(MotifHints {
+			flags:       0,
+			functions:   0,
+			decorations: 0,
+			input_mode:  MOTIF_INPUT_MODELESS,
+			status:      0,
+		})
+	}
+
+	pub fn flags(&self) -> u32 {
+		self.flags
+	}
+
+	pub fn functions(&self) -> Option<u32> {
+		if self.flags & MOTIF_HINT_FUNCTIONS != 0 {
+			Some(self.functions)
+		}
+		else {
+			None
+		}
+	}
+
+	pub fn decorations(&self) -> Option<u32> {
+		if self.flags & MOTIF_HINT_DECORATIONS != 0 {
+			Some(self.decorations)
+		}
+		else {
+			None
+		}
+	}
+
+	pub fn input_mode(&self) -> Option<MotifInputMode> {
+		if self.flags & MOTIF_HINT_INPUT_MODE != 0 {
+			Some(self.input_mode)
+		}
+		else {
+			None
+		}
+	}
+
+	pub fn status(&self) -> Option<u32> {
+		if self.flags & MOTIF_HINT_STATUS != 0 {
+			Some(self.status)
+		}
+		else {
+			None
+		}
+	}
+
+	/// Whether the window wants the decoration, resolving `MOTIF_DECORATION_ALL`.
+	pub fn has_decoration(&self, decoration: u32) -> bool {
+		match self.decorations() {
+			None =>
+				true,
+
+			Some(value) if value & MOTIF_DECORATION_ALL != 0 =>
+				value & decoration == 0,
+
+			Some(value) =>
+				value & decoration != 0,
+		}
+	}
+
+	/// Whether the window allows the function, resolving `MOTIF_FUNCTION_ALL`.
+	pub fn has_function(&self, function: u32) -> bool {
+		match self.functions() {
+			None =>
+				true,
+
+			Some(value) if value & MOTIF_FUNCTION_ALL != 0 =>
+				value & function == 0,
+
+			Some(value) =>
+				value & function != 0,
+		}
+	}
+
+	fn data(&self) -> [u32; 5] {
+		[self.flags, self.functions, self.decorations, self.input_mode as u32, self.status]
+	}
+}
+
+impl MotifHintsBuilder {
+	pub fn functions(mut self, value: u32) -> Self {
+		self.0.flags    |= MOTIF_HINT_FUNCTIONS;
+		self.0.functions = value;
+
+		self
+	}
+
+	pub fn decorations(mut self, value: u32) -> Self {
+		self.0.flags      |= MOTIF_HINT_DECORATIONS;
+		self.0.decorations = value;
+
+		self
+	}
+
+	pub fn input_mode(mut self, value: MotifInputMode) -> Self {
+		self.0.flags     |= MOTIF_HINT_INPUT_MODE;
+		self.0.input_mode = value;
+
+		self
+	}
+
+	pub fn status(mut self, value: u32) -> Self {
+		self.0.flags |= MOTIF_HINT_STATUS;
+		self.0.status = value;
+
+		self
+	}
+
+	pub fn build(self) -> MotifHints {
+		self.0
+	}
+}
+
+pub struct GetMotifHintsCookie<'a>(xcb::GetPropertyCookie<'a>);
+
+impl<'a> GetMotifHintsCookie<'a> {
+	pub fn get_reply(&self) -> Result<MotifHints, xcb::ReplyError> {
+		let reply = property_reply(&self.0)?;
+
+		if reply.format() != 32 || reply.value_len() == 0 {
+			return Err(xcb::ReplyError::NullResponse);
+		}
+
+		// Some clients write fewer than the five fields, the rest default to
+		// zero.
+		let mut data = [0u32; 5];
+		for (field, &value) in data.iter_mut().zip(reply.value::<u32>()) {
+			*field = value;
+		}
+
+		Ok(MotifHints {
+			flags:       data[0],
+			functions:   data[1],
+			decorations: data[2],
+			input_mode:  data[3] as MotifInputMode,
+			status:      data[4],
+		})
+	}
+}
+
+cookie!(GetMotifHintsCookie => MotifHints, |this| (this.0.conn.get_raw_conn(), this.0.cookie));
+
+pub fn set_motif_hints<'a>(c: &'a xcb::Connection, window: xcb::Window, motif_wm_hints: xcb::Atom, hints: &MotifHints) -> xcb::VoidCookie<'a> {
+	xcb::change_property(c, xcb::PROP_MODE_REPLACE as u8, window, motif_wm_hints, motif_wm_hints, 32, &hints.data())
+}
+
+pub fn set_motif_hints_checked<'a>(c: &'a xcb::Connection, window: xcb::Window, motif_wm_hints: xcb::Atom, hints: &MotifHints) -> xcb::VoidCookie<'a> {
+	xcb::change_property_checked(c, xcb::PROP_MODE_REPLACE as u8, window, motif_wm_hints, motif_wm_hints, 32, &hints.data())
+}
+
+pub fn get_motif_hints(c: &xcb::Connection, window: xcb::Window, motif_wm_hints: xcb::Atom) -> GetMotifHintsCookie {
+	GetMotifHintsCookie(xcb::get_property(c, false, window, motif_wm_hints, motif_wm_hints, 0, 5))
+}
+
+pub fn get_motif_hints_unchecked(c: &xcb::Connection, window: xcb::Window, motif_wm_hints: xcb::Atom) -> GetMotifHintsCookie {
+	GetMotifHintsCookie(xcb::get_property_unchecked(c, false, window, motif_wm_hints, motif_wm_hints, 0, 5))
+}
+
+// Takes the reply without consuming the cookie, like the cookies made by
+// `define!`.
+fn property_reply(cookie: &xcb::GetPropertyCookie) -> Result<xcb::GetPropertyReply, xcb::ReplyError> {
+	unsafe {
+		let mut err: *mut xcb_generic_error_t = ptr::null_mut();
+		let reply = xcb_get_property_reply(cookie.conn.get_raw_conn(), cookie.cookie,
+			if cookie.checked { &mut err } else { ptr::null_mut() });
+
+		if !err.is_null() {
+			Err(xcb::ReplyError::GenericError(xcb::GenericError { ptr: err }))
+		}
+		else if reply.is_null() {
+			Err(xcb::ReplyError::NullResponse)
+		}
+		else {
+			Ok(xcb::GetPropertyReply { ptr: reply })
+		}
+	}
+}
+
 define!(cookie GetWmProtocolsCookie with xcb_icccm_get_wm_protocols_reply => GetWmProtocolsReply);
 define!(reply GetWmProtocolsReply for xcb_icccm_get_wm_protocols_reply_t with xcb_icccm_get_wm_protocols_reply_wipe);
 
@@ -656,9 +909,9 @@ pub struct GetWmStateCookie<'a>(xcb::GetPropertyCookie<'a>);
 pub struct GetWmStateReply(xcb::GetPropertyReply);
 
 impl<'a> GetWmStateCookie<'a> {
-	pub fn get_reply(self) -> Result<GetWmStateReply, xcb::ReplyError> {
-		let reply = self.0.get_reply()?;
-		
+	pub fn get_reply(&self) -> Result<GetWmStateReply, xcb::ReplyError> {
+		let reply = property_reply(&self.0)?;
+
 		if reply.type_() == xcb::ATOM_NONE {
 			Err(xcb::ReplyError::GenericError(xcb::GenericError { ptr: ptr::null_mut() }))
 		}
@@ -668,6 +921,8 @@ impl<'a> GetWmStateCookie<'a> {
 	}
 }
 
+cookie!(GetWmStateCookie => GetWmStateReply, |this| (this.0.conn.get_raw_conn(), this.0.cookie));
+
 impl GetWmStateReply {
 	pub fn state(&self) -> WmState {
 		self.0.value()[0]
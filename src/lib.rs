@@ -8,9 +8,17 @@ pub mod ffi;
 #[macro_use]
 mod util;
 
+pub mod cookie;
+
+#[cfg(feature = "async")]
+pub mod reactor;
+
 #[cfg(feature = "icccm")]
 pub mod icccm;
 
+#[cfg(feature = "xembed")]
+pub mod xembed;
+
 #[cfg(feature = "ewmh")]
 pub mod ewmh;
 
@@ -28,3 +36,30 @@ pub mod misc;
 
 #[cfg(feature = "render")]
 pub mod render;
+
+#[cfg(feature = "manager")]
+pub mod manager;
+
+#[cfg(feature = "client")]
+pub mod client;
+
+#[cfg(feature = "desktop")]
+pub mod desktop;
+
+#[cfg(feature = "sync")]
+pub mod sync;
+
+#[cfg(feature = "startup")]
+pub mod startup;
+
+#[cfg(feature = "tray")]
+pub mod tray;
+
+#[cfg(feature = "xdnd")]
+pub mod xdnd;
+
+#[cfg(feature = "selection")]
+pub mod selection;
+
+#[cfg(feature = "errors")]
+pub mod errors;
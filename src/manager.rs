@@ -0,0 +1,226 @@
+use std::fmt;
+use std::error;
+use std::time::{Duration, Instant};
+
+use xcb;
+use ewmh;
+use util::event;
+
+#[derive(Debug)]
+pub enum Error {
+	/// The requested screen does not exist.
+	InvalidScreen(ewmh::InvalidScreen),
+
+	/// The selection is already owned by the given window and no replacement
+	/// was requested.
+	Owned(xcb::Window),
+
+	/// The selection owner did not change to our window, usually because the
+	/// timestamp is older than the last ownership change.
+	Refused,
+
+	/// The previous owner did not destroy its window in time.
+	Timeout(xcb::Window),
+
+	Reply(xcb::ReplyError),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::InvalidScreen(ref err) =>
+				err.fmt(f),
+
+			Error::Owned(window) =>
+				write!(f, "selection already owned by 0x{:x}", window),
+
+			Error::Refused =>
+				f.write_str("selection ownership refused"),
+
+			Error::Timeout(window) =>
+				write!(f, "previous owner 0x{:x} did not go away", window),
+
+			Error::Reply(ref err) =>
+				err.fmt(f),
+		}
+	}
+}
+
+impl error::Error for Error { }
+
+impl From<xcb::ReplyError> for Error {
+	fn from(value: xcb::ReplyError) -> Self {
+		Error::Reply(value)
+	}
+}
+
+impl From<ewmh::InvalidScreen> for Error {
+	fn from(value: ewmh::InvalidScreen) -> Self {
+		Error::InvalidScreen(value)
+	}
+}
+
+/// An owned manager selection (`WM_Sn`, `_NET_WM_CM_Sn`,
+/// `_NET_SYSTEM_TRAY_Sn`, ...) as described in ICCCM section 2.8.
+///
+/// Ownership is released when the value is dropped.
+pub struct Manager<'a> {
+	conn:      &'a ewmh::Connection,
+	selection: xcb::Atom,
+	window:    xcb::Window,
+	root:      xcb::Window,
+	timestamp: xcb::Timestamp,
+	previous:  Option<xcb::Window>,
+}
+
+impl<'a> Manager<'a> {
+	pub fn selection(&self) -> xcb::Atom {
+		self.selection
+	}
+
+	pub fn window(&self) -> xcb::Window {
+		self.window
+	}
+
+	pub fn root(&self) -> xcb::Window {
+		self.root
+	}
+
+	pub fn timestamp(&self) -> xcb::Timestamp {
+		self.timestamp
+	}
+
+	/// The manager that was replaced, if any.
+	pub fn previous(&self) -> Option<xcb::Window> {
+		self.previous
+	}
+
+	/// Checks whether the event is a `SelectionClear` taking the selection
+	/// away from us, in which case the manager should shut down.
+	pub fn is_lost(&self, event: &xcb::GenericEvent) -> bool {
+		if event.response_type() & !0x80 != xcb::SELECTION_CLEAR {
+			return false;
+		}
+
+		let event: &xcb::SelectionClearEvent = unsafe {
+			xcb::cast_event(event)
+		};
+
+		event.selection() == self.selection && event.owner() == self.window
+	}
+}
+
+impl<'a> Drop for Manager<'a> {
+	fn drop(&mut self) {
+		// If someone else took over in the meantime their timestamp is newer
+		// and the server ignores this.
+		xcb::set_selection_owner(self.conn, xcb::NONE, self.selection, self.timestamp);
+		self.conn.flush();
+	}
+}
+
+/// Interns the `<prefix><screen>` selection atom, e.g. `WM_S0`.
+pub fn selection(c: &xcb::Connection, prefix: &str, screen: ewmh::Screen) -> Result<xcb::Atom, xcb::ReplyError> {
+	Ok(xcb::intern_atom(c, false, &format!("{}{}", prefix, screen.index())).get_reply()?.atom())
+}
+
+pub fn wm_selection(c: &xcb::Connection, screen: ewmh::Screen) -> Result<xcb::Atom, xcb::ReplyError> {
+	selection(c, "WM_S", screen)
+}
+
+pub fn cm_selection(c: &ewmh::Connection, screen: ewmh::Screen) -> xcb::Atom {
+	c.WM_CM()[screen.index() as usize]
+}
+
+pub fn tray_selection(c: &xcb::Connection, screen: ewmh::Screen) -> Result<xcb::Atom, xcb::ReplyError> {
+	selection(c, "_NET_SYSTEM_TRAY_S", screen)
+}
+
+/// Acquires the manager selection for the given screen with `window` as
+/// owner.
+///
+/// If the selection is already owned and `replace` is `None` the acquisition
+/// fails, otherwise the previous owner is given the duration to destroy its
+/// window before giving up. On success the `MANAGER` client message is
+/// broadcast on the root window.
+///
+/// Any other event received while waiting for the previous owner is pushed
+/// to `skipped` for the caller to handle.
+///
+/// The timestamp should be a real server timestamp, not `CurrentTime`.
+pub fn acquire<'a>(c: &'a ewmh::Connection, screen: ewmh::Screen, selection: xcb::Atom, window: xcb::Window, timestamp: xcb::Timestamp, replace: Option<Duration>, skipped: &mut Vec<xcb::GenericEvent>) -> Result<Manager<'a>, Error> {
+	let root = screen.root();
+
+	let previous = match xcb::get_selection_owner(c, selection).get_reply()?.owner() {
+		xcb::NONE =>
+			None,
+
+		owner if replace.is_none() =>
+			return Err(Error::Owned(owner)),
+
+		owner =>
+			Some(owner),
+	};
+
+	// As ICCCM 2.8 asks, watch the previous owner before taking over so its
+	// `DestroyNotify` can't be missed. If it's already gone there's nothing
+	// to wait for.
+	let previous = previous.filter(|&previous|
+		xcb::change_window_attributes_checked(c, previous,
+			&[(xcb::CW_EVENT_MASK, xcb::EVENT_MASK_STRUCTURE_NOTIFY)]).request_check().is_ok());
+
+	xcb::set_selection_owner(c, window, selection, timestamp);
+
+	let manager = Manager {
+		conn:      c,
+		selection: selection,
+		window:    window,
+		root:      root,
+		timestamp: timestamp,
+		previous:  previous,
+	};
+
+	if xcb::get_selection_owner(c, selection).get_reply()?.owner() != window {
+		return Err(Error::Refused);
+	}
+
+	if let (Some(previous), Some(timeout)) = (previous, replace) {
+		destroyed(c, previous, Instant::now() + timeout, skipped)?;
+	}
+
+	let data  = xcb::ClientMessageData::from_data32([timestamp, selection, window, 0, 0]);
+	let event = xcb::ClientMessageEvent::new(32, root, c.MANAGER(), data);
+	xcb::send_event(c, false, root, xcb::EVENT_MASK_STRUCTURE_NOTIFY, &event);
+	c.flush();
+
+	Ok(manager)
+}
+
+// Waits for the `DestroyNotify` of the window, which must have
+// `StructureNotify` selected.
+fn destroyed(c: &xcb::Connection, window: xcb::Window, deadline: Instant, skipped: &mut Vec<xcb::GenericEvent>) -> Result<(), Error> {
+	while let Some(event) = event::poll(c, deadline)? {
+		if event.response_type() & !0x80 == xcb::DESTROY_NOTIFY {
+			let notify: &xcb::DestroyNotifyEvent = unsafe { xcb::cast_event(&event) };
+
+			if notify.window() == window {
+				return Ok(());
+			}
+		}
+
+		skipped.push(event);
+	}
+
+	Err(Error::Timeout(window))
+}
+
+/// Decodes a `MANAGER` client message as sent to the root window when a new
+/// manager takes over a selection, returning the selection and its owner.
+pub fn announcement(c: &ewmh::Connection, event: &xcb::ClientMessageEvent) -> Option<(xcb::Atom, xcb::Window)> {
+	if event.type_() != c.MANAGER() || event.format() != 32 {
+		return None;
+	}
+
+	let data = event.data().data32();
+	Some((data[1], data[2]))
+}
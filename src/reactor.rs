@@ -0,0 +1,250 @@
+//! Awaitable cookies, enabled by the `async` feature.
+//!
+//! Every cookie can be `.await`ed, a background thread watches the file
+//! descriptor of the connections with pending replies and wakes the tasks
+//! waiting on them, so the executor is never blocked.
+//!
+//! The feature enables `thread`, so the futures are `Send` and can be spawned
+//! on multi-threaded executors.
+//!
+//! Awaiting a cookie sends an extra `GetInputFocus` request and waits for its
+//! reply too, as the reply functions of the utility libraries can only block.
+//!
+//! Reading events from the connection while replies are awaited, as with
+//! `poll_for_event` or `wait_for_event`, may read the replies as well, and the
+//! descriptor won't become readable for them again. Call `wake` after reading
+//! events so the waiting tasks check again.
+
+use std::future::Future;
+use std::io;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::pin::Pin;
+use std::ptr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use xcb::{self, CookieSeq};
+use xcb::ffi::*;
+use libc;
+use ffi::poll::*;
+use cookie::PropertyCookie;
+
+/// A cookie whose reply can be awaited.
+pub trait Request: PropertyCookie {
+	fn connection(&self) -> *mut xcb_connection_t;
+}
+
+/// Resolves to the reply of a cookie.
+pub struct ReplyFuture<C: Request> {
+	cookie:   Option<C>,
+	sentinel: Option<u32>,
+}
+
+impl<C: Request> ReplyFuture<C> {
+	pub fn new(cookie: C) -> ReplyFuture<C> {
+		ReplyFuture {
+			cookie:   Some(cookie),
+			sentinel: None,
+		}
+	}
+}
+
+impl<C: Request + Unpin> Future for ReplyFuture<C> {
+	type Output = Result<C::Reply, xcb::ReplyError>;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+		let c = self.cookie.as_ref().expect("polled after completion").connection();
+
+		// The reply functions of the utility libraries only come in blocking
+		// flavours, but replies arrive in order, so once the reply to a request
+		// sent after the cookie's is in, the cookie's own can be taken without
+		// blocking.
+		let sentinel = match self.sentinel {
+			Some(sentinel) =>
+				sentinel,
+
+			None => unsafe {
+				let sentinel = xcb_get_input_focus(c).sequence();
+				xcb_flush(c);
+
+				self.sentinel = Some(sentinel);
+				sentinel
+			}
+		};
+
+		// A broken connection never delivers the reply, and its descriptor may
+		// be closed, so the reply function reports the error right away.
+		if unsafe { xcb_connection_has_error(c) } != 0 {
+			self.sentinel = None;
+			return Poll::Ready(self.cookie.take().unwrap().get_reply());
+		}
+
+		if !arrived(c, sentinel) {
+			register(unsafe { xcb_get_file_descriptor(c) }, cx.waker());
+
+			// It may have been read in the meantime, and then the descriptor
+			// isn't going to become readable for it.
+			if !arrived(c, sentinel) {
+				return Poll::Pending;
+			}
+		}
+
+		self.sentinel = None;
+		Poll::Ready(self.cookie.take().unwrap().get_reply())
+	}
+}
+
+impl<C: Request> Drop for ReplyFuture<C> {
+	fn drop(&mut self) {
+		if let (Some(cookie), Some(sentinel)) = (self.cookie.as_ref(), self.sentinel) {
+			unsafe {
+				xcb_discard_reply(cookie.connection(), sentinel);
+			}
+		}
+	}
+}
+
+fn arrived(c: *mut xcb_connection_t, sequence: u32) -> bool {
+	unsafe {
+		let mut reply = ptr::null_mut();
+		let mut error = ptr::null_mut();
+
+		if xcb_poll_for_reply(c, sequence, &mut reply, &mut error) == 0 {
+			return false;
+		}
+
+		libc::free(reply);
+		libc::free(error as *mut _);
+
+		true
+	}
+}
+
+struct Reactor {
+	waiting: Arc<Mutex<Vec<(RawFd, Waker)>>>,
+	notify:  RawFd,
+}
+
+static REACTOR: OnceLock<Reactor> = OnceLock::new();
+
+fn reactor() -> &'static Reactor {
+	REACTOR.get_or_init(|| {
+		let mut pipe = [0; 2];
+
+		unsafe {
+			if libc::pipe(pipe.as_mut_ptr()) != 0 {
+				panic!("cannot create the reactor pipe");
+			}
+
+			libc::fcntl(pipe[0], libc::F_SETFL, libc::O_NONBLOCK);
+		}
+
+		let waiting = Arc::new(Mutex::new(Vec::new()));
+		let shared  = waiting.clone();
+
+		thread::Builder::new().name("xcb-util reactor".into())
+			.spawn(move || run(pipe[0], &shared))
+			.expect("cannot spawn the reactor thread");
+
+		Reactor {
+			waiting: waiting,
+			notify:  pipe[1],
+		}
+	})
+}
+
+// Wakes the task once the descriptor becomes readable.
+fn register(fd: RawFd, waker: &Waker) {
+	let reactor = reactor();
+
+	{
+		let mut waiting = reactor.waiting.lock().unwrap();
+
+		if waiting.iter().any(|&(other, ref w)| other == fd && w.will_wake(waker)) {
+			return;
+		}
+
+		waiting.push((fd, waker.clone()));
+	}
+
+	unsafe {
+		libc::write(reactor.notify, &1u8 as *const u8 as *const _, 1);
+	}
+}
+
+/// Wakes every task awaiting a reply on the connection, to be called after
+/// reading events from it elsewhere.
+pub fn wake(c: &xcb::Connection) {
+	let reactor = match REACTOR.get() {
+		Some(reactor) =>
+			reactor,
+
+		None =>
+			return
+	};
+
+	let fd    = unsafe { xcb_get_file_descriptor(c.get_raw_conn()) };
+	let woken = {
+		let mut waiting = reactor.waiting.lock().unwrap();
+		let (woken, rest) = waiting.drain(..).partition::<Vec<_>, _>(|&(other, _)| other == fd);
+		*waiting = rest;
+
+		woken
+	};
+
+	for (_, waker) in woken {
+		waker.wake();
+	}
+}
+
+fn run(notify: RawFd, waiting: &Mutex<Vec<(RawFd, Waker)>>) {
+	loop {
+		let mut fds = vec![libc::pollfd { fd: notify, events: libc::POLLIN, revents: 0 }];
+		fds.extend(waiting.lock().unwrap().iter()
+			.map(|&(fd, _)| libc::pollfd { fd: fd, events: libc::POLLIN, revents: 0 }));
+
+		if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) } < 0 {
+			if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+				continue;
+			}
+
+			// Retrying would fail the same way, so every task is woken to find
+			// out from its connection instead.
+			let woken = mem::take(&mut *waiting.lock().unwrap());
+
+			for (_, waker) in woken {
+				waker.wake();
+			}
+
+			continue;
+		}
+
+		if fds[0].revents != 0 {
+			let mut buffer = [0u8; 64];
+			while unsafe { libc::read(notify, buffer.as_mut_ptr() as *mut _, buffer.len()) } > 0 { }
+		}
+
+		// `POLLERR`, `POLLHUP` and `POLLNVAL` remove the waiters just like
+		// `POLLIN`, the tasks then find their connection broken instead of
+		// waiting on the descriptor again.
+		let ready = fds[1 ..].iter().filter(|p| p.revents != 0).map(|p| p.fd).collect::<Vec<_>>();
+
+		if ready.is_empty() {
+			continue;
+		}
+
+		let woken = {
+			let mut waiting = waiting.lock().unwrap();
+			let (woken, rest) = waiting.drain(..).partition::<Vec<_>, _>(|&(fd, _)| ready.contains(&fd));
+			*waiting = rest;
+
+			woken
+		};
+
+		for (_, waker) in woken {
+			waker.wake();
+		}
+	}
+}
@@ -0,0 +1,647 @@
+use std::fmt;
+use std::error;
+use std::collections::HashMap;
+
+use xcb;
+use ewmh;
+
+#[derive(Debug)]
+pub enum Error {
+	/// The selection owner did not change to our window.
+	Refused,
+
+	/// The data is not a whole number of items of its format, or the format
+	/// is not 8, 16 or 32.
+	InvalidData {
+		format: u8,
+		length: usize,
+	},
+
+	Reply(xcb::ReplyError),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::Refused =>
+				f.write_str("selection ownership refused"),
+
+			Error::InvalidData { format, length } =>
+				write!(f, "{} bytes are not valid data of format {}", length, format),
+
+			Error::Reply(ref err) =>
+				err.fmt(f),
+		}
+	}
+}
+
+impl error::Error for Error { }
+
+impl From<xcb::ReplyError> for Error {
+	fn from(value: xcb::ReplyError) -> Self {
+		Error::Reply(value)
+	}
+}
+
+/// The atoms used by selections.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Atoms {
+	pub clipboard:   xcb::Atom,
+	pub targets:     xcb::Atom,
+	pub multiple:    xcb::Atom,
+	pub timestamp:   xcb::Atom,
+	pub incr:        xcb::Atom,
+	pub atom_pair:   xcb::Atom,
+	pub utf8_string: xcb::Atom,
+
+	/// The property conversions are requested into.
+	pub property: xcb::Atom,
+}
+
+impl Atoms {
+	pub fn intern(c: &ewmh::Connection) -> Result<Atoms, xcb::ReplyError> {
+		let clipboard = xcb::intern_atom(c, false, "CLIPBOARD");
+		let targets   = xcb::intern_atom(c, false, "TARGETS");
+		let multiple  = xcb::intern_atom(c, false, "MULTIPLE");
+		let timestamp = xcb::intern_atom(c, false, "TIMESTAMP");
+		let incr      = xcb::intern_atom(c, false, "INCR");
+		let atom_pair = xcb::intern_atom(c, false, "ATOM_PAIR");
+		let property  = xcb::intern_atom(c, false, "_XCB_UTIL_SELECTION");
+
+		Ok(Atoms {
+			clipboard:   clipboard.get_reply()?.atom(),
+			targets:     targets.get_reply()?.atom(),
+			multiple:    multiple.get_reply()?.atom(),
+			timestamp:   timestamp.get_reply()?.atom(),
+			incr:        incr.get_reply()?.atom(),
+			atom_pair:   atom_pair.get_reply()?.atom(),
+			utf8_string: c.UTF8_STRING(),
+
+			property: property.get_reply()?.atom(),
+		})
+	}
+}
+
+/// The value of a selection target.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Data {
+	pub kind:   xcb::Atom,
+	pub format: u8,
+	pub bytes:  Vec<u8>,
+}
+
+impl Data {
+	/// Data in format 8, as used by text and MIME targets.
+	pub fn new(kind: xcb::Atom, bytes: Vec<u8>) -> Data {
+		Data {
+			kind:   kind,
+			format: 8,
+			bytes:  bytes,
+		}
+	}
+
+	/// Decodes `UTF8_STRING` and `STRING` data, the latter is Latin-1.
+	pub fn text(&self, atoms: &Atoms) -> Option<String> {
+		if self.format != 8 {
+			return None;
+		}
+
+		if self.kind == atoms.utf8_string {
+			String::from_utf8(self.bytes.clone()).ok()
+		}
+		else if self.kind == xcb::ATOM_STRING {
+			Some(self.bytes.iter().map(|&b| b as char).collect())
+		}
+		else {
+			None
+		}
+	}
+
+	fn check(&self) -> Result<(), Error> {
+		let size = match self.format {
+			8  => 1,
+			16 => 2,
+			32 => 4,
+			_  => 0,
+		};
+
+		if size == 0 || self.bytes.len() % size != 0 {
+			return Err(Error::InvalidData { format: self.format, length: self.bytes.len() });
+		}
+
+		Ok(())
+	}
+}
+
+// The data must have passed `Data::check`, trailing bytes are ignored.
+fn put<'a>(c: &'a xcb::Connection, mode: u8, window: xcb::Window, property: xcb::Atom, kind: xcb::Atom, format: u8, bytes: &[u8]) -> xcb::VoidCookie<'a> {
+	match format {
+		16 => xcb::change_property(c, mode, window, property, kind, 16, &bytes.chunks_exact(2)
+			.map(|b| u16::from_ne_bytes([b[0], b[1]])).collect::<Vec<_>>()),
+
+		32 => xcb::change_property(c, mode, window, property, kind, 32, &bytes.chunks_exact(4)
+			.map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]])).collect::<Vec<_>>()),
+
+		_ => xcb::change_property(c, mode, window, property, kind, 8, bytes),
+	}
+}
+
+fn take(c: &xcb::Connection, window: xcb::Window, property: xcb::Atom) -> Result<Data, xcb::ReplyError> {
+	let reply = xcb::get_property(c, true, window, property, xcb::GET_PROPERTY_TYPE_ANY, 0, u32::MAX / 4).get_reply()?;
+
+	let bytes = match reply.format() {
+		16 => reply.value::<u16>().iter().flat_map(|v| v.to_ne_bytes().to_vec()).collect(),
+		32 => reply.value::<u32>().iter().flat_map(|v| v.to_ne_bytes().to_vec()).collect(),
+		_  => reply.value::<u8>().to_vec(),
+	};
+
+	Ok(Data {
+		kind:   reply.type_(),
+		format: reply.format(),
+		bytes:  bytes,
+	})
+}
+
+struct Transfer {
+	requestor: xcb::Window,
+	property:  xcb::Atom,
+	data:      Data,
+	offset:    usize,
+}
+
+/// What an event meant to the owner.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OwnerEvent {
+	/// A conversion was answered, or an incremental one progressed.
+	Handled,
+
+	/// Another client took the selection.
+	Lost,
+}
+
+/// An owned selection answering conversion requests from other clients.
+///
+/// `TARGETS`, `MULTIPLE` and `TIMESTAMP` are answered automatically, payloads
+/// larger than what fits in a single request are sent with INCR.
+pub struct Owner<'a> {
+	conn:      &'a xcb::Connection,
+	atoms:     Atoms,
+	selection: xcb::Atom,
+	window:    xcb::Window,
+	timestamp: xcb::Timestamp,
+	targets:   HashMap<xcb::Atom, Data>,
+	transfers: Vec<Transfer>,
+	chunk:     usize,
+
+	// The foreign requestors we selected `PropertyChange` on, with the number
+	// of transfers to them and the mask we had selected before.
+	watched: HashMap<xcb::Window, (usize, u32)>,
+}
+
+impl<'a> Owner<'a> {
+	/// Acquires the selection, the timestamp must be a real server timestamp.
+	pub fn acquire(c: &'a xcb::Connection, atoms: &Atoms, selection: xcb::Atom, window: xcb::Window, timestamp: xcb::Timestamp) -> Result<Owner<'a>, Error> {
+		xcb::set_selection_owner(c, window, selection, timestamp);
+
+		if xcb::get_selection_owner(c, selection).get_reply()?.owner() != window {
+			return Err(Error::Refused);
+		}
+
+		// The maximum length is in 4 byte units, use a quarter of it in bytes
+		// as most clients do.
+		let chunk = c.get_maximum_request_length() as usize & !3;
+
+		Ok(Owner {
+			conn:      c,
+			atoms:     *atoms,
+			selection: selection,
+			window:    window,
+			timestamp: timestamp,
+			targets:   HashMap::new(),
+			transfers: Vec::new(),
+			chunk:     chunk,
+			watched:   HashMap::new(),
+		})
+	}
+
+	pub fn selection(&self) -> xcb::Atom {
+		self.selection
+	}
+
+	pub fn window(&self) -> xcb::Window {
+		self.window
+	}
+
+	pub fn timestamp(&self) -> xcb::Timestamp {
+		self.timestamp
+	}
+
+	/// Offers data for the target, replacing any previous value.
+	pub fn set(&mut self, target: xcb::Atom, data: Data) -> Result<(), Error> {
+		data.check()?;
+		self.targets.insert(target, data);
+
+		Ok(())
+	}
+
+	/// Offers the text as `UTF8_STRING` and `STRING`.
+	pub fn set_text(&mut self, text: &str) {
+		let utf8   = self.atoms.utf8_string;
+		let latin1 = text.chars().map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' }).collect();
+
+		self.targets.insert(utf8, Data::new(utf8, text.as_bytes().to_vec()));
+		self.targets.insert(xcb::ATOM_STRING, Data::new(xcb::ATOM_STRING, latin1));
+	}
+
+	/// Stops offering all targets.
+	pub fn clear(&mut self) {
+		self.targets.clear();
+	}
+
+	/// Handles `SelectionRequest`, `SelectionClear` and the `PropertyNotify`
+	/// events driving incremental transfers, `None` if the event is not for
+	/// us.
+	pub fn handle(&mut self, event: &xcb::GenericEvent) -> Option<OwnerEvent> {
+		match event.response_type() & !0x80 {
+			xcb::SELECTION_REQUEST => {
+				let event: &xcb::SelectionRequestEvent = unsafe { xcb::cast_event(event) };
+
+				if event.owner() != self.window || event.selection() != self.selection {
+					return None;
+				}
+
+				self.request(event);
+				Some(OwnerEvent::Handled)
+			}
+
+			xcb::SELECTION_CLEAR => {
+				let event: &xcb::SelectionClearEvent = unsafe { xcb::cast_event(event) };
+
+				if event.owner() != self.window || event.selection() != self.selection {
+					return None;
+				}
+
+				self.abort();
+				Some(OwnerEvent::Lost)
+			}
+
+			xcb::PROPERTY_NOTIFY => {
+				let event: &xcb::PropertyNotifyEvent = unsafe { xcb::cast_event(event) };
+
+				if event.state() != xcb::PROPERTY_DELETE as u8 {
+					return None;
+				}
+
+				let index = self.transfers.iter()
+					.position(|t| t.requestor == event.window() && t.property == event.atom())?;
+
+				self.step(index);
+				Some(OwnerEvent::Handled)
+			}
+
+			_ =>
+				None
+		}
+	}
+
+	fn request(&mut self, event: &xcb::SelectionRequestEvent) {
+		// Obsolete clients pass no property, the target is used instead.
+		let property = if event.property() == xcb::NONE { event.target() } else { event.property() };
+		let time     = event.time();
+
+		let converted = if time != xcb::CURRENT_TIME && time < self.timestamp {
+			false
+		}
+		else if event.target() == self.atoms.multiple {
+			self.multiple(event.requestor(), property)
+		}
+		else {
+			self.convert(event.requestor(), event.target(), property)
+		};
+
+		let notify = xcb::SelectionNotifyEvent::new(time, event.requestor(), event.selection(), event.target(),
+			if converted { property } else { xcb::NONE });
+
+		xcb::send_event(self.conn, false, event.requestor(), xcb::EVENT_MASK_NO_EVENT, &notify);
+		self.conn.flush();
+	}
+
+	fn multiple(&mut self, requestor: xcb::Window, property: xcb::Atom) -> bool {
+		let pairs = match xcb::get_property(self.conn, false, requestor, property, self.atoms.atom_pair, 0, u32::MAX / 4).get_reply() {
+			Ok(ref reply) if reply.format() == 32 => reply.value::<u32>().to_vec(),
+			_ => return false,
+		};
+
+		let mut result = pairs.clone();
+
+		for (i, pair) in pairs.chunks(2).enumerate() {
+			if pair.len() != 2 || pair[0] == self.atoms.multiple || !self.convert(requestor, pair[0], pair[1]) {
+				result[i * 2 + 1] = xcb::NONE;
+			}
+		}
+
+		xcb::change_property(self.conn, xcb::PROP_MODE_REPLACE as u8, requestor, property, self.atoms.atom_pair, 32, &result);
+		true
+	}
+
+	fn convert(&mut self, requestor: xcb::Window, target: xcb::Atom, property: xcb::Atom) -> bool {
+		if target == self.atoms.targets {
+			let mut targets = vec![self.atoms.targets, self.atoms.multiple, self.atoms.timestamp];
+			targets.extend(self.targets.keys());
+
+			xcb::change_property(self.conn, xcb::PROP_MODE_REPLACE as u8, requestor, property, xcb::ATOM_ATOM, 32, &targets);
+			return true;
+		}
+
+		if target == self.atoms.timestamp {
+			xcb::change_property(self.conn, xcb::PROP_MODE_REPLACE as u8, requestor, property, xcb::ATOM_INTEGER, 32, &[self.timestamp]);
+			return true;
+		}
+
+		let data = match self.targets.get(&target) {
+			Some(data) => data.clone(),
+			None => return false,
+		};
+
+		if data.bytes.len() <= self.chunk {
+			put(self.conn, xcb::PROP_MODE_REPLACE as u8, requestor, property, data.kind, data.format, &data.bytes);
+			return true;
+		}
+
+		// Too big for a single request, announce the size and send chunks
+		// every time the requestor deletes the property.
+		self.watch(requestor);
+		xcb::change_property(self.conn, xcb::PROP_MODE_REPLACE as u8, requestor, property, self.atoms.incr, 32, &[data.bytes.len() as u32]);
+
+		if let Some(index) = self.transfers.iter().position(|t| t.requestor == requestor && t.property == property) {
+			self.transfers.remove(index);
+			self.unwatch(requestor);
+		}
+
+		self.transfers.push(Transfer {
+			requestor: requestor,
+			property:  property,
+			data:      data,
+			offset:    0,
+		});
+
+		true
+	}
+
+	fn step(&mut self, index: usize) {
+		let done = {
+			let transfer = &mut self.transfers[index];
+			let end      = (transfer.offset + self.chunk).min(transfer.data.bytes.len());
+
+			// The final empty chunk marks the end of the transfer.
+			put(self.conn, xcb::PROP_MODE_REPLACE as u8, transfer.requestor, transfer.property,
+				transfer.data.kind, transfer.data.format, &transfer.data.bytes[transfer.offset .. end]);
+
+			let done = transfer.offset == end;
+			transfer.offset = end;
+
+			done
+		};
+
+		if done {
+			let transfer = self.transfers.remove(index);
+			self.unwatch(transfer.requestor);
+		}
+
+		self.conn.flush();
+	}
+
+	// Selects `PropertyChange` on a requestor of another client for as long
+	// as transfers to it are going on, on top of whatever we had selected.
+	// Our own windows are left alone, they select it themselves.
+	fn watch(&mut self, requestor: xcb::Window) {
+		let setup = self.conn.get_setup();
+
+		if requestor & !setup.resource_id_mask() == setup.resource_id_base() {
+			return;
+		}
+
+		if let Some(entry) = self.watched.get_mut(&requestor) {
+			entry.0 += 1;
+			return;
+		}
+
+		let previous = xcb::get_window_attributes(self.conn, requestor).get_reply()
+			.map(|reply| reply.your_event_mask()).unwrap_or(xcb::EVENT_MASK_NO_EVENT);
+
+		xcb::change_window_attributes(self.conn, requestor,
+			&[(xcb::CW_EVENT_MASK, previous | xcb::EVENT_MASK_PROPERTY_CHANGE)]);
+
+		self.watched.insert(requestor, (1, previous));
+	}
+
+	// Restores the previous mask once the last transfer to the requestor is
+	// over.
+	fn unwatch(&mut self, requestor: xcb::Window) {
+		let last = match self.watched.get_mut(&requestor) {
+			Some(entry) => {
+				entry.0 -= 1;
+				entry.0 == 0
+			}
+
+			None =>
+				return,
+		};
+
+		if last {
+			let (_, previous) = self.watched.remove(&requestor).unwrap();
+			xcb::change_window_attributes(self.conn, requestor, &[(xcb::CW_EVENT_MASK, previous)]);
+		}
+	}
+
+	fn abort(&mut self) {
+		self.transfers.clear();
+
+		for (requestor, (_, previous)) in self.watched.drain() {
+			xcb::change_window_attributes(self.conn, requestor, &[(xcb::CW_EVENT_MASK, previous)]);
+		}
+
+		self.conn.flush();
+	}
+}
+
+impl<'a> Drop for Owner<'a> {
+	fn drop(&mut self) {
+		self.abort();
+		xcb::set_selection_owner(self.conn, xcb::NONE, self.selection, self.timestamp);
+		self.conn.flush();
+	}
+}
+
+/// A conversion of a selection by us as requestor.
+///
+/// The window must select `PropertyChange` events for incremental transfers
+/// to work.
+pub struct Request<'a> {
+	conn:      &'a xcb::Connection,
+	atoms:     Atoms,
+	window:    xcb::Window,
+	selection: xcb::Atom,
+	target:    xcb::Atom,
+	incr:      Option<Data>,
+}
+
+impl<'a> Request<'a> {
+	/// Asks the owner to convert the selection to the target.
+	pub fn new(c: &'a xcb::Connection, atoms: &Atoms, window: xcb::Window, selection: xcb::Atom, target: xcb::Atom, timestamp: xcb::Timestamp) -> Request<'a> {
+		xcb::convert_selection(c, window, selection, target, atoms.property, timestamp);
+		c.flush();
+
+		Request {
+			conn:      c,
+			atoms:     *atoms,
+			window:    window,
+			selection: selection,
+			target:    target,
+			incr:      None,
+		}
+	}
+
+	pub fn selection(&self) -> xcb::Atom {
+		self.selection
+	}
+
+	pub fn target(&self) -> xcb::Atom {
+		self.target
+	}
+
+	/// Feeds an event, returning `Some` once the conversion is over with the
+	/// data or `None` if the owner refused it.
+	pub fn handle(&mut self, event: &xcb::GenericEvent) -> Option<Result<Option<Data>, xcb::ReplyError>> {
+		match event.response_type() & !0x80 {
+			xcb::SELECTION_NOTIFY => {
+				let event: &xcb::SelectionNotifyEvent = unsafe { xcb::cast_event(event) };
+
+				if event.requestor() != self.window || event.selection() != self.selection || event.target() != self.target {
+					return None;
+				}
+
+				if event.property() == xcb::NONE {
+					return Some(Ok(None));
+				}
+
+				// Deleting the property, done by `take`, tells the owner to
+				// start sending the chunks.
+				let data = match take(self.conn, self.window, event.property()) {
+					Ok(data) => data,
+					Err(err) => return Some(Err(err)),
+				};
+
+				if data.kind == self.atoms.incr {
+					self.incr = Some(Data { kind: xcb::NONE, format: 8, bytes: Vec::new() });
+					self.conn.flush();
+
+					return None;
+				}
+
+				Some(Ok(Some(data)))
+			}
+
+			xcb::PROPERTY_NOTIFY => {
+				let event: &xcb::PropertyNotifyEvent = unsafe { xcb::cast_event(event) };
+
+				if self.incr.is_none() || event.window() != self.window || event.atom() != self.atoms.property
+					|| event.state() != xcb::PROPERTY_NEW_VALUE as u8
+				{
+					return None;
+				}
+
+				let chunk = match take(self.conn, self.window, self.atoms.property) {
+					Ok(chunk) => chunk,
+					Err(err) => return Some(Err(err)),
+				};
+
+				self.conn.flush();
+
+				if chunk.bytes.is_empty() {
+					return Some(Ok(self.incr.take()));
+				}
+
+				let data = self.incr.as_mut()?;
+				data.kind   = chunk.kind;
+				data.format = chunk.format;
+				data.bytes.extend(chunk.bytes);
+
+				None
+			}
+
+			_ =>
+				None
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn atoms() -> Atoms {
+		Atoms {
+			clipboard:   1,
+			targets:     2,
+			multiple:    3,
+			timestamp:   4,
+			incr:        5,
+			atom_pair:   6,
+			utf8_string: 7,
+
+			property: 8,
+		}
+	}
+
+	fn data(format: u8, length: usize) -> Data {
+		Data { kind: xcb::ATOM_INTEGER, format, bytes: vec![0; length] }
+	}
+
+	#[test]
+	fn check() {
+		assert!(data(8, 3).check().is_ok());
+		assert!(data(16, 4).check().is_ok());
+		assert!(data(32, 8).check().is_ok());
+		assert!(data(32, 0).check().is_ok());
+
+		match data(16, 3).check() {
+			Err(Error::InvalidData { format: 16, length: 3 }) => (),
+			other => panic!("unexpected {:?}", other),
+		}
+
+		match data(32, 6).check() {
+			Err(Error::InvalidData { format: 32, length: 6 }) => (),
+			other => panic!("unexpected {:?}", other),
+		}
+
+		match data(24, 6).check() {
+			Err(Error::InvalidData { format: 24, length: 6 }) => (),
+			other => panic!("unexpected {:?}", other),
+		}
+	}
+
+	#[test]
+	fn latin1() {
+		let atoms = atoms();
+		let data  = Data::new(xcb::ATOM_STRING, vec![b'c', b'a', b'f', 0xe9, b' ', 0xff]);
+
+		assert_eq!(data.text(&atoms).as_deref(), Some("café ÿ"));
+	}
+
+	#[test]
+	fn utf8() {
+		let atoms = atoms();
+
+		assert_eq!(Data::new(atoms.utf8_string, "café".into()).text(&atoms).as_deref(), Some("café"));
+		assert_eq!(Data::new(atoms.utf8_string, vec![b'c', 0xe9]).text(&atoms), None);
+	}
+
+	#[test]
+	fn not_text() {
+		let atoms = atoms();
+
+		assert_eq!(Data::new(atoms.targets, b"text".to_vec()).text(&atoms), None);
+		assert_eq!(Data { kind: xcb::ATOM_STRING, format: 16, bytes: vec![0; 4] }.text(&atoms), None);
+	}
+}
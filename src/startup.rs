@@ -0,0 +1,405 @@
+use std::str;
+use std::collections::HashMap;
+
+use xcb;
+use ewmh;
+
+/// The type of a startup notification message.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+	/// `new:`, a launch has started.
+	New,
+
+	/// `change:`, details about a launch changed.
+	Change,
+
+	/// `remove:`, the launch has completed or failed.
+	Remove,
+}
+
+impl Kind {
+	fn prefix(&self) -> &'static str {
+		match *self {
+			Kind::New    => "new:",
+			Kind::Change => "change:",
+			Kind::Remove => "remove:",
+		}
+	}
+}
+
+/// A startup notification message, a type followed by key/value pairs.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Message {
+	kind:   Kind,
+	values: Vec<(String, String)>,
+}
+
+impl Message {
+	/// Creates a message for the launch with the given ID.
+	pub fn new<T: Into<String>>(kind: Kind, id: T) -> Message {
+		Message {
+			kind:   kind,
+			values: vec![("ID".to_owned(), id.into())],
+		}
+	}
+
+	/// Sets the value for the key, replacing any previous value.
+	pub fn set<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+		let (key, value) = (key.into(), value.into());
+
+		if let Some(pair) = self.values.iter_mut().find(|pair| pair.0 == key) {
+			pair.1 = value;
+			return self;
+		}
+
+		self.values.push((key, value));
+		self
+	}
+
+	pub fn kind(&self) -> Kind {
+		self.kind
+	}
+
+	pub fn get(&self, key: &str) -> Option<&str> {
+		self.values.iter().find(|pair| pair.0 == key).map(|pair| pair.1.as_ref())
+	}
+
+	pub fn values(&self) -> &[(String, String)] {
+		&self.values
+	}
+
+	pub fn id(&self) -> Option<&str> {
+		self.get("ID")
+	}
+
+	pub fn name(&self) -> Option<&str> {
+		self.get("NAME")
+	}
+
+	pub fn screen(&self) -> Option<i32> {
+		self.get("SCREEN").and_then(|v| v.parse().ok())
+	}
+
+	pub fn desktop(&self) -> Option<u32> {
+		self.get("DESKTOP").and_then(|v| v.parse().ok())
+	}
+
+	pub fn timestamp(&self) -> Option<xcb::Timestamp> {
+		self.get("TIMESTAMP").and_then(|v| v.parse().ok())
+	}
+
+	/// Encodes the message, quoting values as needed.
+	pub fn encode(&self) -> String {
+		let mut result = self.kind.prefix().to_owned();
+
+		for (key, value) in &self.values {
+			result.push(' ');
+			result.push_str(key);
+			result.push('=');
+
+			if !value.is_empty() && !value.contains(&[' ', '"', '\\'][..]) {
+				result.push_str(value);
+				continue;
+			}
+
+			result.push('"');
+
+			for c in value.chars() {
+				if c == '"' || c == '\\' {
+					result.push('\\');
+				}
+
+				result.push(c);
+			}
+
+			result.push('"');
+		}
+
+		result
+	}
+
+	/// Parses an encoded message, `None` if the type is unknown or a pair is
+	/// malformed.
+	pub fn parse(message: &str) -> Option<Message> {
+		let (kind, rest) = [Kind::New, Kind::Change, Kind::Remove].iter()
+			.filter_map(|&kind| message.strip_prefix(kind.prefix()).map(|rest| (kind, rest)))
+			.next()?;
+
+		let mut values = Vec::new();
+		let mut chars  = rest.chars().peekable();
+
+		loop {
+			while chars.peek() == Some(&' ') {
+				chars.next();
+			}
+
+			if chars.peek().is_none() {
+				break;
+			}
+
+			let mut key = String::new();
+
+			loop {
+				match chars.next() {
+					Some('=') => break,
+					Some(' ') | None => return None,
+					Some(c) => key.push(c),
+				}
+			}
+
+			// Quotes can start and end anywhere in the value and a backslash
+			// escapes the next character both inside and outside of them.
+			let mut value  = String::new();
+			let mut quoted = false;
+
+			loop {
+				match chars.peek().cloned() {
+					None =>
+						break,
+
+					Some(' ') if !quoted =>
+						break,
+
+					Some('"') => {
+						chars.next();
+						quoted = !quoted;
+					}
+
+					Some('\\') => {
+						chars.next();
+						value.push(chars.next()?);
+					}
+
+					Some(c) => {
+						chars.next();
+						value.push(c);
+					}
+				}
+			}
+
+			values.push((key, value));
+		}
+
+		Some(Message {
+			kind:   kind,
+			values: values,
+		})
+	}
+}
+
+/// The atoms used by the protocol.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Atoms {
+	pub begin:      xcb::Atom,
+	pub info:       xcb::Atom,
+	pub startup_id: xcb::Atom,
+}
+
+impl Atoms {
+	pub fn intern(c: &xcb::Connection) -> Result<Atoms, xcb::ReplyError> {
+		let begin      = xcb::intern_atom(c, false, "_NET_STARTUP_INFO_BEGIN");
+		let info       = xcb::intern_atom(c, false, "_NET_STARTUP_INFO");
+		let startup_id = xcb::intern_atom(c, false, "_NET_STARTUP_ID");
+
+		Ok(Atoms {
+			begin:      begin.get_reply()?.atom(),
+			info:       info.get_reply()?.atom(),
+			startup_id: startup_id.get_reply()?.atom(),
+		})
+	}
+}
+
+/// Sends the message to the root window of the screen split in 20 byte
+/// client messages.
+///
+/// The window identifies the sender and must stay alive until the message
+/// has been sent, launchers usually create a dedicated unmapped window.
+pub fn send(c: &xcb::Connection, atoms: &Atoms, root: xcb::Window, window: xcb::Window, message: &Message) {
+	// The messages are format 8, which `ewmh::send_client_message` can't send.
+	let mut data = message.encode().into_bytes();
+	data.push(0);
+
+	for (i, chunk) in data.chunks(20).enumerate() {
+		let mut bytes = [0u8; 20];
+		bytes[.. chunk.len()].copy_from_slice(chunk);
+
+		let kind  = if i == 0 { atoms.begin } else { atoms.info };
+		let event = xcb::ClientMessageEvent::new(8, window, kind, xcb::ClientMessageData::from_data8(bytes));
+		xcb::send_event(c, false, root, xcb::EVENT_MASK_PROPERTY_CHANGE, &event);
+	}
+}
+
+/// The longest encoded message accepted by `Decoder::feed`, longer ones are
+/// dropped.
+pub const MAX_MESSAGE_LENGTH: usize = 16 * 1024;
+
+/// Reassembles messages from the client messages received on the root
+/// window.
+#[derive(Default)]
+pub struct Decoder {
+	pending: HashMap<xcb::Window, Vec<u8>>,
+}
+
+impl Decoder {
+	pub fn new() -> Decoder {
+		Decoder::default()
+	}
+
+	/// Feeds a client message, returning the message it completes if any.
+	pub fn feed(&mut self, atoms: &Atoms, event: &xcb::ClientMessageEvent) -> Option<Message> {
+		if event.format() != 8 {
+			return None;
+		}
+
+		let window = event.window();
+
+		if event.type_() == atoms.begin {
+			self.pending.insert(window, Vec::new());
+		}
+		else if event.type_() != atoms.info {
+			return None;
+		}
+
+		let done = {
+			let buffer = self.pending.get_mut(&window)?;
+			let data   = event.data().data8();
+			let end    = data.iter().position(|&b| b == 0);
+
+			buffer.extend_from_slice(&data[.. end.unwrap_or(data.len())]);
+
+			if buffer.len() > MAX_MESSAGE_LENGTH {
+				self.pending.remove(&window);
+				return None;
+			}
+
+			end.is_some()
+		};
+
+		if !done {
+			return None;
+		}
+
+		let buffer = self.pending.remove(&window)?;
+		Message::parse(str::from_utf8(&buffer).ok()?)
+	}
+
+	/// Forgets any partial message from a window that went away.
+	pub fn forget(&mut self, window: xcb::Window) {
+		self.pending.remove(&window);
+	}
+}
+
+/// Reads the `_NET_STARTUP_ID` of a client.
+pub fn get_startup_id(c: &ewmh::Connection, atoms: &Atoms, window: xcb::Window) -> Result<Option<String>, xcb::ReplyError> {
+	let reply = xcb::get_property(c, false, window, atoms.startup_id, c.UTF8_STRING(), 0, u32::MAX).get_reply()?;
+
+	if reply.format() != 8 || reply.value_len() == 0 {
+		return Ok(None);
+	}
+
+	Ok(str::from_utf8(reply.value::<u8>()).ok().map(|id| id.to_owned()))
+}
+
+pub fn set_startup_id<'a>(c: &'a ewmh::Connection, atoms: &Atoms, window: xcb::Window, id: &str) -> xcb::VoidCookie<'a> {
+	xcb::change_property(c, xcb::PROP_MODE_REPLACE as u8, window, atoms.startup_id, c.UTF8_STRING(), 8, id.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn atoms() -> Atoms {
+		Atoms {
+			begin:      1,
+			info:       2,
+			startup_id: 3,
+		}
+	}
+
+	fn chunks(window: xcb::Window, message: &str) -> Vec<xcb::ClientMessageEvent> {
+		let mut data = message.as_bytes().to_vec();
+		data.push(0);
+
+		data.chunks(20).enumerate().map(|(i, chunk)| {
+			let mut bytes = [0u8; 20];
+			bytes[.. chunk.len()].copy_from_slice(chunk);
+
+			xcb::ClientMessageEvent::new(8, window, if i == 0 { 1 } else { 2 },
+				xcb::ClientMessageData::from_data8(bytes))
+		}).collect()
+	}
+
+	#[test]
+	fn encode_quotes() {
+		let message = Message::new(Kind::New, "id")
+			.set("NAME", "two words")
+			.set("PATH", "a\\b\"c")
+			.set("EMPTY", "");
+
+		assert_eq!(message.encode(), r#"new: ID=id NAME="two words" PATH="a\\b\"c" EMPTY="""#);
+		assert_eq!(Message::parse(&message.encode()), Some(message));
+	}
+
+	#[test]
+	fn parse_quotes() {
+		let message = Message::parse(r#"change: ID=x NAME=foo" bar"baz ICON=a\ b"#).unwrap();
+
+		assert_eq!(message.kind(), Kind::Change);
+		assert_eq!(message.id(), Some("x"));
+		assert_eq!(message.name(), Some("foo barbaz"));
+		assert_eq!(message.get("ICON"), Some("a b"));
+	}
+
+	#[test]
+	fn parse_malformed() {
+		assert_eq!(Message::parse("unknown: ID=x"), None);
+		assert_eq!(Message::parse("new: ID"), None);
+		assert_eq!(Message::parse(r#"new: ID=x\"#), None);
+	}
+
+	#[test]
+	fn reassemble() {
+		let atoms = atoms();
+		let mut decoder = Decoder::new();
+		let first = chunks(10, "new: ID=first NAME=\"a long name for the first launch\"");
+		let second = chunks(20, "remove: ID=second");
+
+		assert!(first.len() > 1);
+
+		for event in &first[.. first.len() - 1] {
+			assert_eq!(decoder.feed(&atoms, event), None);
+		}
+
+		let message = second.iter().filter_map(|event| decoder.feed(&atoms, event)).next().unwrap();
+		assert_eq!(message.kind(), Kind::Remove);
+		assert_eq!(message.id(), Some("second"));
+
+		let message = decoder.feed(&atoms, first.last().unwrap()).unwrap();
+		assert_eq!(message.kind(), Kind::New);
+		assert_eq!(message.name(), Some("a long name for the first launch"));
+	}
+
+	#[test]
+	fn continuation_without_begin() {
+		let atoms = atoms();
+		let mut decoder = Decoder::new();
+		let events = chunks(10, "new: ID=a NAME=\"something longer\"");
+
+		assert_eq!(decoder.feed(&atoms, &events[1]), None);
+	}
+
+	#[test]
+	fn forget_and_limit() {
+		let atoms = atoms();
+		let mut decoder = Decoder::new();
+		let events = chunks(10, "new: ID=a NAME=\"something longer\"");
+
+		decoder.feed(&atoms, &events[0]);
+		decoder.forget(10);
+		assert_eq!(decoder.feed(&atoms, &events[1]), None);
+
+		let long = format!("new: ID=a NAME={}", "x".repeat(MAX_MESSAGE_LENGTH));
+		assert!(chunks(10, &long).iter().all(|event| decoder.feed(&atoms, event).is_none()));
+		assert!(decoder.pending.is_empty());
+	}
+}
@@ -0,0 +1,303 @@
+use std::fmt;
+use std::error;
+use std::time::{Duration, Instant};
+
+use xcb;
+use xcb::sync;
+use xcb::ffi::sync::*;
+use icccm;
+use ewmh;
+use util::event;
+
+#[derive(Debug)]
+pub enum Error {
+	/// The server does not support the SYNC extension.
+	Unsupported,
+
+	/// The client does not take part in the `_NET_WM_SYNC_REQUEST` protocol.
+	NotSupported(xcb::Window),
+
+	Reply(xcb::ReplyError),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::Unsupported =>
+				f.write_str("SYNC extension not available"),
+
+			Error::NotSupported(window) =>
+				write!(f, "window 0x{:x} does not support _NET_WM_SYNC_REQUEST", window),
+
+			Error::Reply(ref err) =>
+				err.fmt(f),
+		}
+	}
+}
+
+impl error::Error for Error { }
+
+impl From<xcb::ReplyError> for Error {
+	fn from(value: xcb::ReplyError) -> Self {
+		Error::Reply(value)
+	}
+}
+
+/// Initializes the SYNC extension, must be done once per connection before
+/// using counters.
+pub fn initialize(c: &xcb::Connection) -> Result<(), Error> {
+	if !c.get_extension_data(sync::id()).map(|e| e.present()).unwrap_or(false) {
+		return Err(Error::Unsupported);
+	}
+
+	sync::initialize(c, sync::MAJOR_VERSION as u8, sync::MINOR_VERSION as u8).get_reply()?;
+	Ok(())
+}
+
+/// Checks whether the window lists `_NET_WM_SYNC_REQUEST` in its
+/// `WM_PROTOCOLS`.
+pub fn supports_sync_request(c: &ewmh::Connection, window: xcb::Window) -> Result<bool, xcb::ReplyError> {
+	Ok(icccm::get_wm_protocols(c, window, c.WM_PROTOCOLS()).get_reply()?
+		.atoms().contains(&c.WM_SYNC_REQUEST()))
+}
+
+fn to_int64(value: u64) -> sync::Int64 {
+	sync::Int64::new((value >> 32) as i32, value as u32)
+}
+
+fn from_int64(value: sync::Int64) -> u64 {
+	((value.hi() as u32 as u64) << 32) | value.lo() as u64
+}
+
+/// The window manager side of `_NET_WM_SYNC_REQUEST`.
+///
+/// Before each configure the value is bumped and sent to the client, which
+/// sets its counter to it once it has redrawn.
+pub struct SyncRequest<'a> {
+	conn:    &'a ewmh::Connection,
+	window:  xcb::Window,
+	counter: sync::Counter,
+	value:   u64,
+}
+
+impl<'a> SyncRequest<'a> {
+	/// Sets up the protocol for the client, failing if it does not advertise
+	/// it or has no counter.
+	pub fn new(c: &'a ewmh::Connection, window: xcb::Window) -> Result<SyncRequest<'a>, Error> {
+		initialize(c)?;
+
+		let protocols = icccm::get_wm_protocols(c, window, c.WM_PROTOCOLS());
+		let counter   = xcb::get_property(c, false, window, c.WM_SYNC_REQUEST_COUNTER(), xcb::ATOM_CARDINAL, 0, 2);
+
+		if !protocols.get_reply()?.atoms().contains(&c.WM_SYNC_REQUEST()) {
+			return Err(Error::NotSupported(window));
+		}
+
+		// The property holds the counter XID, not a 64-bit value.
+		let reply   = counter.get_reply()?;
+		let counter = match reply.value::<u32>().first() {
+			Some(&counter) if counter != xcb::NONE && reply.format() == 32 =>
+				counter,
+
+			_ =>
+				return Err(Error::NotSupported(window)),
+		};
+
+		let value = from_int64(sync::query_counter(c, counter).get_reply()?.counter_value());
+
+		Ok(SyncRequest {
+			conn:    c,
+			window:  window,
+			counter: counter,
+			value:   value,
+		})
+	}
+
+	pub fn window(&self) -> xcb::Window {
+		self.window
+	}
+
+	pub fn counter(&self) -> sync::Counter {
+		self.counter
+	}
+
+	/// The value the counter has to reach for the last request to be done.
+	pub fn value(&self) -> u64 {
+		self.value
+	}
+
+	/// Bumps the value and sends it to the client, this should be done right
+	/// before sending the `ConfigureNotify`.
+	pub fn send(&mut self, timestamp: xcb::Timestamp) -> u64 {
+		self.value = self.value.wrapping_add(1);
+
+		ewmh::send_wm_sync_request(self.conn, self.window, self.conn.WM_PROTOCOLS(),
+			self.conn.WM_SYNC_REQUEST(), timestamp, self.value);
+		self.conn.flush();
+
+		self.value
+	}
+
+	/// Checks whether the client has caught up with the last request.
+	pub fn is_done(&self) -> Result<bool, xcb::ReplyError> {
+		let current = from_int64(sync::query_counter(self.conn, self.counter).get_reply()?.counter_value());
+		Ok(current >= self.value)
+	}
+
+	/// Waits for the client to catch up with the last request, returning
+	/// `false` if it did not in time.
+	///
+	/// An alarm on the counter reports when that happens, any other event
+	/// received while waiting is pushed to `skipped` for the caller to handle.
+	pub fn wait(&self, timeout: Duration, skipped: &mut Vec<xcb::GenericEvent>) -> Result<bool, xcb::ReplyError> {
+		let deadline = Instant::now() + timeout;
+		let notify   = self.conn.get_extension_data(sync::id()).map(|e| e.first_event()).unwrap_or(0) + sync::ALARM_NOTIFY;
+		let alarm    = self.conn.generate_id();
+
+		let values = xcb_sync_create_alarm_value_list_t {
+			counter:    self.counter,
+			value_type: sync::VALUETYPE_ABSOLUTE,
+			value:      to_int64(self.value).base,
+			test_type:  sync::TESTTYPE_POSITIVE_COMPARISON,
+			delta:      to_int64(0).base,
+			events:     1,
+		};
+
+		// With every attribute given the value list has the layout of the
+		// struct. The alarm triggers right away if the counter is already
+		// there.
+		let c: &xcb::Connection = self.conn;
+
+		void!(checked -> c,
+			xcb_sync_create_alarm_checked(c.get_raw_conn(), alarm,
+				sync::CA_COUNTER | sync::CA_VALUE_TYPE | sync::CA_VALUE | sync::CA_TEST_TYPE | sync::CA_DELTA | sync::CA_EVENTS,
+				&values)).request_check()?;
+
+		let result = (|| {
+			while let Some(event) = event::poll(self.conn, deadline)? {
+				if event.response_type() & !0x80 == notify {
+					let notify: &sync::AlarmNotifyEvent = unsafe { xcb::cast_event(&event) };
+
+					if notify.alarm() == alarm {
+						return Ok(from_int64(notify.counter_value()) >= self.value);
+					}
+				}
+
+				skipped.push(event);
+			}
+
+			Ok(false)
+		})();
+
+		sync::destroy_alarm(self.conn, alarm);
+		self.conn.flush();
+
+		result
+	}
+}
+
+/// The client side of `_NET_WM_SYNC_REQUEST`.
+///
+/// The counter is published in `_NET_WM_SYNC_REQUEST_COUNTER` and
+/// `_NET_WM_SYNC_REQUEST` is added to `WM_PROTOCOLS` if missing. Both are
+/// removed when dropped.
+pub struct SyncCounter<'a> {
+	conn:     &'a ewmh::Connection,
+	window:   xcb::Window,
+	counter:  sync::Counter,
+	pending:  Option<u64>,
+	protocol: bool,
+}
+
+impl<'a> SyncCounter<'a> {
+	/// Creates the counter and publishes it on the window.
+	pub fn create(c: &'a ewmh::Connection, window: xcb::Window) -> Result<SyncCounter<'a>, Error> {
+		initialize(c)?;
+
+		// Read before anything is created, so nothing is left behind if it
+		// fails.
+		let mut protocols = wm_protocols(c, window)?;
+		let     protocol  = !protocols.contains(&c.WM_SYNC_REQUEST());
+
+		let counter = c.generate_id();
+		sync::create_counter_checked(c, counter, to_int64(0)).request_check()?;
+
+		xcb::change_property(c, xcb::PROP_MODE_REPLACE as u8, window,
+			c.WM_SYNC_REQUEST_COUNTER(), xcb::ATOM_CARDINAL, 32, &[counter]);
+
+		if protocol {
+			protocols.push(c.WM_SYNC_REQUEST());
+			icccm::set_wm_protocols(c, window, c.WM_PROTOCOLS(), &protocols);
+		}
+
+		Ok(SyncCounter {
+			conn:     c,
+			window:   window,
+			counter:  counter,
+			pending:  None,
+			protocol: protocol,
+		})
+	}
+
+	pub fn window(&self) -> xcb::Window {
+		self.window
+	}
+
+	pub fn counter(&self) -> sync::Counter {
+		self.counter
+	}
+
+	/// The value of the last request not yet acknowledged.
+	pub fn pending(&self) -> Option<u64> {
+		self.pending
+	}
+
+	/// Records the value if the event is a sync request for our window,
+	/// returning whether it was.
+	pub fn handle(&mut self, event: &xcb::ClientMessageEvent) -> bool {
+		if event.window() != self.window || event.type_() != self.conn.WM_PROTOCOLS() || event.format() != 32 {
+			return false;
+		}
+
+		let data = event.data().data32();
+
+		if data[0] != self.conn.WM_SYNC_REQUEST() {
+			return false;
+		}
+
+		self.pending = Some((data[3] as u64) << 32 | data[2] as u64);
+		true
+	}
+
+	/// Acknowledges the last request, to be called once the frame following
+	/// the `ConfigureNotify` has been drawn.
+	pub fn update(&mut self) {
+		if let Some(value) = self.pending.take() {
+			sync::set_counter(self.conn, self.counter, to_int64(value));
+			self.conn.flush();
+		}
+	}
+}
+
+impl<'a> Drop for SyncCounter<'a> {
+	fn drop(&mut self) {
+		// Only take back what we added, the rest belongs to the client.
+		if self.protocol {
+			if let Ok(mut protocols) = wm_protocols(self.conn, self.window) {
+				protocols.retain(|&atom| atom != self.conn.WM_SYNC_REQUEST());
+				icccm::set_wm_protocols(self.conn, self.window, self.conn.WM_PROTOCOLS(), &protocols);
+			}
+		}
+
+		xcb::delete_property(self.conn, self.window, self.conn.WM_SYNC_REQUEST_COUNTER());
+		sync::destroy_counter(self.conn, self.counter);
+		self.conn.flush();
+	}
+}
+
+// The atoms in `WM_PROTOCOLS`, empty if the property is missing.
+fn wm_protocols(c: &ewmh::Connection, window: xcb::Window) -> Result<Vec<xcb::Atom>, xcb::ReplyError> {
+	let reply = xcb::get_property(c, false, window, c.WM_PROTOCOLS(), xcb::ATOM_ATOM, 0, u32::MAX).get_reply()?;
+
+	Ok(if reply.format() == 32 { reply.value::<xcb::Atom>().to_vec() } else { Vec::new() })
+}
@@ -0,0 +1,386 @@
+use std::convert::TryFrom;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use xcb;
+use ewmh;
+use manager::{self, Manager};
+
+pub const REQUEST_DOCK:   u32 = 0;
+pub const BEGIN_MESSAGE:  u32 = 1;
+pub const CANCEL_MESSAGE: u32 = 2;
+
+/// The longest balloon message accepted by `Tray::handle`, longer ones are
+/// dropped.
+pub const MAX_MESSAGE_LENGTH: usize = 64 * 1024;
+
+pub type Orientation = u32;
+pub const ORIENTATION_HORZ: Orientation = 0;
+pub const ORIENTATION_VERT: Orientation = 1;
+
+/// The atoms used by the protocol on a screen.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Atoms {
+	pub selection:    xcb::Atom,
+	pub opcode:       xcb::Atom,
+	pub message_data: xcb::Atom,
+	pub orientation:  xcb::Atom,
+	pub visual:       xcb::Atom,
+}
+
+impl Atoms {
+	pub fn intern(c: &xcb::Connection, screen: ewmh::Screen) -> Result<Atoms, xcb::ReplyError> {
+		let selection    = xcb::intern_atom(c, false, &format!("_NET_SYSTEM_TRAY_S{}", screen.index()));
+		let opcode       = xcb::intern_atom(c, false, "_NET_SYSTEM_TRAY_OPCODE");
+		let message_data = xcb::intern_atom(c, false, "_NET_SYSTEM_TRAY_MESSAGE_DATA");
+		let orientation  = xcb::intern_atom(c, false, "_NET_SYSTEM_TRAY_ORIENTATION");
+		let visual       = xcb::intern_atom(c, false, "_NET_SYSTEM_TRAY_VISUAL");
+
+		Ok(Atoms {
+			selection:    selection.get_reply()?.atom(),
+			opcode:       opcode.get_reply()?.atom(),
+			message_data: message_data.get_reply()?.atom(),
+			orientation:  orientation.get_reply()?.atom(),
+			visual:       visual.get_reply()?.atom(),
+		})
+	}
+}
+
+/// A request received by the tray host.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TrayEvent {
+	/// An icon wants to be embedded in the tray.
+	Dock {
+		window:    xcb::Window,
+		timestamp: xcb::Timestamp,
+	},
+
+	/// An icon finished sending a balloon message.
+	Message {
+		window:  xcb::Window,
+		id:      u32,
+		timeout: Option<Duration>,
+		text:    String,
+	},
+
+	/// An icon withdrew a balloon message.
+	Cancel {
+		window: xcb::Window,
+		id:     u32,
+	},
+}
+
+struct Balloon {
+	id:      u32,
+	timeout: u32,
+	length:  usize,
+	data:    Vec<u8>,
+}
+
+impl Balloon {
+	fn finish(self, window: xcb::Window) -> TrayEvent {
+		TrayEvent::Message {
+			window:  window,
+			id:      self.id,
+			timeout: if self.timeout == 0 { None } else { Some(Duration::from_millis(self.timeout as u64)) },
+			text:    String::from_utf8_lossy(&self.data).into_owned(),
+		}
+	}
+}
+
+// Decodes the messages sent to the tray and reassembles balloon messages.
+#[derive(Default)]
+struct Decoder {
+	balloons: HashMap<xcb::Window, Balloon>,
+}
+
+impl Decoder {
+	fn feed(&mut self, atoms: &Atoms, event: &xcb::ClientMessageEvent) -> Option<TrayEvent> {
+		if event.type_() == atoms.message_data && event.format() == 8 {
+			let done = {
+				let balloon = self.balloons.get_mut(&event.window())?;
+				let missing = balloon.length - balloon.data.len();
+				let data    = event.data().data8();
+
+				balloon.data.extend_from_slice(&data[.. missing.min(data.len())]);
+				balloon.data.len() == balloon.length
+			};
+
+			if !done {
+				return None;
+			}
+
+			return self.balloons.remove(&event.window()).map(|b| b.finish(event.window()));
+		}
+
+		if event.type_() != atoms.opcode || event.format() != 32 {
+			return None;
+		}
+
+		let data = event.data().data32();
+
+		match data[1] {
+			REQUEST_DOCK =>
+				Some(TrayEvent::Dock {
+					window:    data[2],
+					timestamp: data[0],
+				}),
+
+			BEGIN_MESSAGE => {
+				let balloon = Balloon {
+					id:      data[4],
+					timeout: data[2],
+					length:  data[3] as usize,
+					data:    Vec::new(),
+				};
+
+				// The length comes from the icon, so nothing is reserved for it up
+				// front and a message too long to be sensible is ignored.
+				if balloon.length > MAX_MESSAGE_LENGTH {
+					self.balloons.remove(&event.window());
+					return None;
+				}
+
+				if balloon.length == 0 {
+					self.balloons.remove(&event.window());
+					return Some(balloon.finish(event.window()));
+				}
+
+				self.balloons.insert(event.window(), balloon);
+				None
+			}
+
+			CANCEL_MESSAGE => {
+				if self.balloons.get(&event.window()).map(|b| b.id) == Some(data[2]) {
+					self.balloons.remove(&event.window());
+				}
+
+				Some(TrayEvent::Cancel {
+					window: event.window(),
+					id:     data[2],
+				})
+			}
+
+			_ =>
+				None
+		}
+	}
+
+	fn forget(&mut self, window: xcb::Window) {
+		self.balloons.remove(&window);
+	}
+}
+
+/// The host side of the tray, owning the `_NET_SYSTEM_TRAY_Sn` selection.
+pub struct Tray<'a> {
+	conn:     &'a ewmh::Connection,
+	manager:  Manager<'a>,
+	atoms:    Atoms,
+	decoder:  Decoder,
+}
+
+impl<'a> Tray<'a> {
+	/// Acquires the tray selection for the screen, see `manager::acquire`.
+	pub fn acquire(c: &'a ewmh::Connection, screen: ewmh::Screen, window: xcb::Window, timestamp: xcb::Timestamp, replace: Option<Duration>, skipped: &mut Vec<xcb::GenericEvent>) -> Result<Tray<'a>, manager::Error> {
+		let atoms   = Atoms::intern(c, screen)?;
+		let manager = manager::acquire(c, screen, atoms.selection, window, timestamp, replace, skipped)?;
+
+		Ok(Tray {
+			conn:     c,
+			manager:  manager,
+			atoms:    atoms,
+			decoder:  Decoder::default(),
+		})
+	}
+
+	pub fn manager(&self) -> &Manager<'a> {
+		&self.manager
+	}
+
+	pub fn atoms(&self) -> &Atoms {
+		&self.atoms
+	}
+
+	pub fn window(&self) -> xcb::Window {
+		self.manager.window()
+	}
+
+	/// Publishes the orientation of the tray.
+	pub fn set_orientation(&self, orientation: Orientation) -> xcb::VoidCookie<'a> {
+		xcb::change_property(self.conn, xcb::PROP_MODE_REPLACE as u8, self.window(),
+			self.atoms.orientation, xcb::ATOM_CARDINAL, 32, &[orientation])
+	}
+
+	/// Publishes the visual icons should use for their windows.
+	pub fn set_visual(&self, visual: xcb::Visualid) -> xcb::VoidCookie<'a> {
+		xcb::change_property(self.conn, xcb::PROP_MODE_REPLACE as u8, self.window(),
+			self.atoms.visual, xcb::ATOM_VISUALID, 32, &[visual])
+	}
+
+	/// Decodes a client message sent to the tray, balloon messages are
+	/// reassembled and only reported once complete.
+	pub fn handle(&mut self, event: &xcb::ClientMessageEvent) -> Option<TrayEvent> {
+		self.decoder.feed(&self.atoms, event)
+	}
+
+	/// Forgets any partial balloon message from an icon that went away.
+	pub fn forget(&mut self, window: xcb::Window) {
+		self.decoder.forget(window);
+	}
+}
+
+/// Finds the window of the tray on the screen, if any.
+pub fn find(c: &xcb::Connection, atoms: &Atoms) -> Result<Option<xcb::Window>, xcb::ReplyError> {
+	Ok(match xcb::get_selection_owner(c, atoms.selection).get_reply()?.owner() {
+		xcb::NONE => None,
+		owner     => Some(owner),
+	})
+}
+
+/// Asks the tray to embed the icon window.
+pub fn request_dock<'a>(c: &'a ewmh::Connection, atoms: &Atoms, tray: xcb::Window, window: xcb::Window, timestamp: xcb::Timestamp) -> xcb::VoidCookie<'a> {
+	send(c, tray, tray, atoms.opcode, [timestamp, REQUEST_DOCK, window, 0, 0])
+}
+
+/// Shows a balloon message next to the icon, a zero timeout never expires.
+pub fn begin_message(c: &ewmh::Connection, atoms: &Atoms, tray: xcb::Window, window: xcb::Window, timestamp: xcb::Timestamp, id: u32, timeout: Duration, text: &str) {
+	let timeout = u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX);
+	send(c, window, tray, atoms.opcode, [timestamp, BEGIN_MESSAGE, timeout, text.len() as u32, id]);
+
+	for chunk in text.as_bytes().chunks(20) {
+		let mut bytes = [0u8; 20];
+		bytes[.. chunk.len()].copy_from_slice(chunk);
+
+		let event = xcb::ClientMessageEvent::new(8, window, atoms.message_data, xcb::ClientMessageData::from_data8(bytes));
+		xcb::send_event(c, false, tray, xcb::EVENT_MASK_NO_EVENT, &event);
+	}
+}
+
+/// Withdraws a balloon message.
+pub fn cancel_message<'a>(c: &'a ewmh::Connection, atoms: &Atoms, tray: xcb::Window, window: xcb::Window, timestamp: xcb::Timestamp, id: u32) -> xcb::VoidCookie<'a> {
+	send(c, window, tray, atoms.opcode, [timestamp, CANCEL_MESSAGE, id, 0, 0])
+}
+
+fn send(c: &xcb::Connection, window: xcb::Window, tray: xcb::Window, kind: xcb::Atom, data: [u32; 5]) -> xcb::VoidCookie {
+	ewmh::send_client_message_direct(c, window, tray, kind, &data)
+}
+
+/// Reads the orientation of the tray.
+pub fn get_orientation(c: &xcb::Connection, atoms: &Atoms, tray: xcb::Window) -> Result<Option<Orientation>, xcb::ReplyError> {
+	let reply = xcb::get_property(c, false, tray, atoms.orientation, xcb::ATOM_CARDINAL, 0, 1).get_reply()?;
+	Ok(if reply.format() == 32 { reply.value::<u32>().first().cloned() } else { None })
+}
+
+/// Reads the visual icons should use, if the tray asks for one.
+pub fn get_visual(c: &xcb::Connection, atoms: &Atoms, tray: xcb::Window) -> Result<Option<xcb::Visualid>, xcb::ReplyError> {
+	let reply = xcb::get_property(c, false, tray, atoms.visual, xcb::ATOM_VISUALID, 0, 1).get_reply()?;
+	Ok(if reply.format() == 32 { reply.value::<u32>().first().cloned() } else { None })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn atoms() -> Atoms {
+		Atoms {
+			selection:    1,
+			opcode:       2,
+			message_data: 3,
+			orientation:  4,
+			visual:       5,
+		}
+	}
+
+	fn opcode(atoms: &Atoms, window: xcb::Window, data: [u32; 5]) -> xcb::ClientMessageEvent {
+		xcb::ClientMessageEvent::new(32, window, atoms.opcode, xcb::ClientMessageData::from_data32(data))
+	}
+
+	fn chunks(atoms: &Atoms, window: xcb::Window, text: &str) -> Vec<xcb::ClientMessageEvent> {
+		text.as_bytes().chunks(20).map(|chunk| {
+			let mut bytes = [0u8; 20];
+			bytes[.. chunk.len()].copy_from_slice(chunk);
+
+			xcb::ClientMessageEvent::new(8, window, atoms.message_data, xcb::ClientMessageData::from_data8(bytes))
+		}).collect()
+	}
+
+	#[test]
+	fn dock() {
+		let atoms       = atoms();
+		let mut decoder = Decoder::default();
+
+		assert_eq!(decoder.feed(&atoms, &opcode(&atoms, 9, [1234, REQUEST_DOCK, 42, 0, 0])),
+			Some(TrayEvent::Dock { window: 42, timestamp: 1234 }));
+	}
+
+	#[test]
+	fn reassemble() {
+		let atoms       = atoms();
+		let mut decoder = Decoder::default();
+		let text        = "a balloon message longer than one chunk";
+
+		assert_eq!(decoder.feed(&atoms, &opcode(&atoms, 42, [0, BEGIN_MESSAGE, 1500, text.len() as u32, 7])), None);
+
+		let mut events = chunks(&atoms, 42, text).into_iter()
+			.map(|event| decoder.feed(&atoms, &event))
+			.collect::<Vec<_>>();
+
+		assert_eq!(events.pop(), Some(Some(TrayEvent::Message {
+			window:  42,
+			id:      7,
+			timeout: Some(Duration::from_millis(1500)),
+			text:    text.to_owned(),
+		})));
+		assert!(events.iter().all(Option::is_none));
+	}
+
+	#[test]
+	fn empty() {
+		let atoms       = atoms();
+		let mut decoder = Decoder::default();
+
+		assert_eq!(decoder.feed(&atoms, &opcode(&atoms, 42, [0, BEGIN_MESSAGE, 0, 0, 7])), Some(TrayEvent::Message {
+			window:  42,
+			id:      7,
+			timeout: None,
+			text:    String::new(),
+		}));
+	}
+
+	#[test]
+	fn cancel() {
+		let atoms       = atoms();
+		let mut decoder = Decoder::default();
+
+		decoder.feed(&atoms, &opcode(&atoms, 42, [0, BEGIN_MESSAGE, 0, 30, 7]));
+		assert_eq!(decoder.feed(&atoms, &opcode(&atoms, 42, [0, CANCEL_MESSAGE, 7, 0, 0])),
+			Some(TrayEvent::Cancel { window: 42, id: 7 }));
+
+		for event in chunks(&atoms, 42, "thirty bytes of cancelled text") {
+			assert_eq!(decoder.feed(&atoms, &event), None);
+		}
+	}
+
+	#[test]
+	fn too_long() {
+		let atoms       = atoms();
+		let mut decoder = Decoder::default();
+
+		decoder.feed(&atoms, &opcode(&atoms, 42, [0, BEGIN_MESSAGE, 0, 2, 7]));
+		decoder.feed(&atoms, &opcode(&atoms, 42, [0, BEGIN_MESSAGE, 0, MAX_MESSAGE_LENGTH as u32 + 1, 8]));
+
+		assert!(decoder.balloons.is_empty());
+		assert_eq!(decoder.feed(&atoms, &chunks(&atoms, 42, "hi")[0]), None);
+	}
+
+	#[test]
+	fn forget() {
+		let atoms       = atoms();
+		let mut decoder = Decoder::default();
+
+		decoder.feed(&atoms, &opcode(&atoms, 42, [0, BEGIN_MESSAGE, 0, 2, 7]));
+		decoder.forget(42);
+
+		assert_eq!(decoder.feed(&atoms, &chunks(&atoms, 42, "hi")[0]), None);
+	}
+}
@@ -1,3 +1,40 @@
+macro_rules! cookie {
+	($cookie:ident => $reply:ty, |$this:ident| ($conn:expr, $inner:expr)) => (
+		impl<'a> ::cookie::Cookie for $cookie<'a> {
+			fn sequence(&self) -> u32 {
+				let $this = self;
+				xcb::CookieSeq::sequence(&$inner)
+			}
+		}
+
+		impl<'a> ::cookie::PropertyCookie for $cookie<'a> {
+			type Reply = $reply;
+
+			fn get_reply(self) -> Result<$reply, xcb::ReplyError> {
+				$cookie::get_reply(&self)
+			}
+		}
+
+		#[cfg(feature = "async")]
+		impl<'a> ::reactor::Request for $cookie<'a> {
+			fn connection(&self) -> *mut xcb_connection_t {
+				let $this = self;
+				$conn
+			}
+		}
+
+		#[cfg(feature = "async")]
+		impl<'a> ::std::future::IntoFuture for $cookie<'a> {
+			type Output     = Result<$reply, xcb::ReplyError>;
+			type IntoFuture = ::reactor::ReplyFuture<$cookie<'a>>;
+
+			fn into_future(self) -> Self::IntoFuture {
+				::reactor::ReplyFuture::new(self)
+			}
+		}
+	);
+}
+
 macro_rules! define {
 	(cookie $cookie:ident for $inner:ident => $reply:ident) => (
 		pub struct $cookie<'a>(xcb::GetPropertyCookie<'a>,
@@ -25,13 +62,20 @@ macro_rules! define {
 					}
 					else {
 						let mut reply = mem::zeroed();
-						self.1(self.0.conn.get_raw_conn(), self.0.cookie, &mut reply, ptr::null_mut());
+						let     res = self.1(self.0.conn.get_raw_conn(), self.0.cookie, &mut reply, ptr::null_mut());
 
-						Ok($reply(reply))
+						if res != 0 {
+							Ok($reply(reply))
+						}
+						else {
+							Err(xcb::ReplyError::NullResponse)
+						}
 					}
 				}
 			}
 		}
+
+		cookie!($cookie => $reply, |this| (this.0.conn.get_raw_conn(), this.0.cookie));
 	);
 
 	(cookie $cookie:ident with $func:ident => $reply:ident) => (
@@ -59,13 +103,61 @@ macro_rules! define {
 					}
 					else {
 						let mut reply = mem::zeroed();
-						$func(self.0.conn.get_raw_conn(), self.0.cookie, &mut reply, ptr::null_mut());
+						let     res = $func(self.0.conn.get_raw_conn(), self.0.cookie, &mut reply, ptr::null_mut());
 
-						Ok($reply(reply))
+						if res != 0 {
+							Ok($reply(reply))
+						}
+						else {
+							Err(xcb::ReplyError::NullResponse)
+						}
 					}
 				}
 			}
 		}
+
+		cookie!($cookie => $reply, |this| (this.0.conn.get_raw_conn(), this.0.cookie));
+	);
+
+	(cookie $cookie:ident with $func:ident as $reply:path) => (
+		pub struct $cookie<'a>(xcb::GetPropertyCookie<'a>);
+
+		#[cfg(feature = "thread")]
+		unsafe impl<'a> Send for $cookie<'a> { }
+		#[cfg(feature = "thread")]
+		unsafe impl<'a> Sync for $cookie<'a> { }
+
+		impl<'a> $cookie<'a> {
+			pub fn get_reply(&self) -> Result<$reply, xcb::ReplyError> {
+				unsafe {
+					if self.0.checked {
+						let mut err: *mut xcb_generic_error_t = ptr::null_mut();
+						let mut reply = mem::zeroed();
+						let     res = $func(self.0.conn.get_raw_conn(), self.0.cookie, &mut reply, &mut err);
+
+						if err.is_null() && res != 0 {
+							Ok(reply)
+						}
+						else {
+							Err(xcb::ReplyError::GenericError(xcb::GenericError { ptr: err }))
+						}
+					}
+					else {
+						let mut reply = mem::zeroed();
+						let     res = $func(self.0.conn.get_raw_conn(), self.0.cookie, &mut reply, ptr::null_mut());
+
+						if res != 0 {
+							Ok(reply)
+						}
+						else {
+							Err(xcb::ReplyError::NullResponse)
+						}
+					}
+				}
+			}
+		}
+
+		cookie!($cookie => $reply, |this| (this.0.conn.get_raw_conn(), this.0.cookie));
 	);
 
 	(cookie $cookie:ident through $conn:ident with $func:ident => $reply:ident) => (
@@ -73,6 +165,7 @@ macro_rules! define {
 			conn:    &'a $conn,
 			cookie:  xcb_get_property_cookie_t,
 			checked: bool,
+			taken:   AtomicBool,
 		}
 
 		#[cfg(feature = "thread")]
@@ -82,6 +175,9 @@ macro_rules! define {
 
 		impl<'a> $cookie<'a> {
 			pub fn get_reply(&self) -> Result<$reply, xcb::ReplyError> {
+				// The reply is consumed whatever the outcome.
+				self.taken.store(true, Ordering::Relaxed);
+
 				unsafe {
 					if self.checked {
 						let mut err: *mut xcb_generic_error_t = ptr::null_mut();
@@ -97,13 +193,30 @@ macro_rules! define {
 					}
 					else {
 						let mut reply = mem::zeroed();
-						$func(self.conn.get_raw_conn(), self.cookie, &mut reply, ptr::null_mut());
+						let     res = $func(self.conn.get_raw_conn(), self.cookie, &mut reply, ptr::null_mut());
 
-						Ok($reply(reply))
+						if res != 0 {
+							Ok($reply(reply))
+						}
+						else {
+							Err(xcb::ReplyError::NullResponse)
+						}
+					}
+				}
+			}
+		}
+
+		impl<'a> Drop for $cookie<'a> {
+			fn drop(&mut self) {
+				if !self.taken.load(Ordering::Relaxed) {
+					unsafe {
+						xcb_discard_reply((**self.conn).get_raw_conn(), xcb::CookieSeq::sequence(&self.cookie));
 					}
 				}
 			}
 		}
+
+		cookie!($cookie => $reply, |this| ((**this.conn).get_raw_conn(), this.cookie));
 	);
 
 	(cookie $cookie:ident through $conn:ident with $func:ident as ($first:path, $second:path)) => (
@@ -111,6 +224,7 @@ macro_rules! define {
 			conn:    &'a $conn,
 			cookie:  xcb_get_property_cookie_t,
 			checked: bool,
+			taken:   AtomicBool,
 		}
 
 		#[cfg(feature = "thread")]
@@ -120,6 +234,9 @@ macro_rules! define {
 
 		impl<'a> $cookie<'a> {
 			pub fn get_reply(&self) -> Result<($first, $second), xcb::ReplyError> {
+				// The reply is consumed whatever the outcome.
+				self.taken.store(true, Ordering::Relaxed);
+
 				unsafe {
 					if self.checked {
 						let mut err: *mut xcb_generic_error_t = ptr::null_mut();
@@ -137,13 +254,30 @@ macro_rules! define {
 					else {
 						let mut first = mem::zeroed();
 						let mut second = mem::zeroed();
-						$func(self.conn.get_raw_conn(), self.cookie, &mut first, &mut second, ptr::null_mut());
+						let     res = $func(self.conn.get_raw_conn(), self.cookie, &mut first, &mut second, ptr::null_mut());
+
+						if res != 0 {
+							Ok((first, second))
+						}
+						else {
+							Err(xcb::ReplyError::NullResponse)
+						}
+					}
+				}
+			}
+		}
 
-						Ok((first, second))
+		impl<'a> Drop for $cookie<'a> {
+			fn drop(&mut self) {
+				if !self.taken.load(Ordering::Relaxed) {
+					unsafe {
+						xcb_discard_reply((**self.conn).get_raw_conn(), xcb::CookieSeq::sequence(&self.cookie));
 					}
 				}
 			}
 		}
+
+		cookie!($cookie => ($first, $second), |this| ((**this.conn).get_raw_conn(), this.cookie));
 	);
 
 	(cookie $cookie:ident through $conn:ident with $func:ident as $reply:path) => (
@@ -151,6 +285,7 @@ macro_rules! define {
 			conn:    &'a $conn,
 			cookie:  xcb_get_property_cookie_t,
 			checked: bool,
+			taken:   AtomicBool,
 		}
 
 		#[cfg(feature = "thread")]
@@ -160,6 +295,9 @@ macro_rules! define {
 
 		impl<'a> $cookie<'a> {
 			pub fn get_reply(&self) -> Result<$reply, xcb::ReplyError> {
+				// The reply is consumed whatever the outcome.
+				self.taken.store(true, Ordering::Relaxed);
+
 				unsafe {
 					if self.checked {
 						let mut err: *mut xcb_generic_error_t = ptr::null_mut();
@@ -175,13 +313,30 @@ macro_rules! define {
 					}
 					else {
 						let mut reply = mem::zeroed();
-						$func(self.conn.get_raw_conn(), self.cookie, &mut reply, ptr::null_mut());
+						let     res = $func(self.conn.get_raw_conn(), self.cookie, &mut reply, ptr::null_mut());
 
-						Ok(reply)
+						if res != 0 {
+							Ok(reply)
+						}
+						else {
+							Err(xcb::ReplyError::NullResponse)
+						}
 					}
 				}
 			}
 		}
+
+		impl<'a> Drop for $cookie<'a> {
+			fn drop(&mut self) {
+				if !self.taken.load(Ordering::Relaxed) {
+					unsafe {
+						xcb_discard_reply((**self.conn).get_raw_conn(), xcb::CookieSeq::sequence(&self.cookie));
+					}
+				}
+			}
+		}
+
+		cookie!($cookie => $reply, |this| ((**this.conn).get_raw_conn(), this.cookie));
 	);
 
 	(cookie $cookie:ident($inner:path) through $conn:ident with $func:ident as $reply:path) => (
@@ -189,6 +344,7 @@ macro_rules! define {
 			conn:    &'a $conn,
 			cookie:  $inner,
 			checked: bool,
+			taken:   AtomicBool,
 		}
 
 		#[cfg(feature = "thread")]
@@ -198,6 +354,9 @@ macro_rules! define {
 
 		impl<'a> $cookie<'a> {
 			pub fn get_reply(&self) -> Result<$reply, xcb::ReplyError> {
+				// The reply is consumed whatever the outcome.
+				self.taken.store(true, Ordering::Relaxed);
+
 				unsafe {
 					if self.checked {
 						let mut err: *mut xcb_generic_error_t = ptr::null_mut();
@@ -213,13 +372,30 @@ macro_rules! define {
 					}
 					else {
 						let mut reply = mem::zeroed();
-						$func(self.conn.get_raw_conn(), self.cookie, &mut reply, ptr::null_mut());
+						let     res = $func(self.conn.get_raw_conn(), self.cookie, &mut reply, ptr::null_mut());
+
+						if res != 0 {
+							Ok(reply)
+						}
+						else {
+							Err(xcb::ReplyError::NullResponse)
+						}
+					}
+				}
+			}
+		}
 
-						Ok(reply)
+		impl<'a> Drop for $cookie<'a> {
+			fn drop(&mut self) {
+				if !self.taken.load(Ordering::Relaxed) {
+					unsafe {
+						xcb_discard_reply((**self.conn).get_raw_conn(), xcb::CookieSeq::sequence(&self.cookie));
 					}
 				}
 			}
 		}
+
+		cookie!($cookie => $reply, |this| ((**this.conn).get_raw_conn(), this.cookie));
 	);
 
 	(reply $reply:ident for $inner:ident with $wipe:ident) => (
@@ -263,6 +439,7 @@ macro_rules! property {
 			conn:    $conn,
 			cookie:  $cookie,
 			checked: true,
+			taken:   AtomicBool::new(false),
 		}
 	});
 
@@ -271,6 +448,7 @@ macro_rules! property {
 			conn:    $conn,
 			cookie:  $cookie,
 			checked: false,
+			taken:   AtomicBool::new(false),
 		}
 	});
 }
@@ -312,3 +490,41 @@ pub mod utf8 {
 		result.into_bytes()
 	}
 }
+
+#[cfg(feature = "ewmh")]
+pub mod event {
+	use std::cmp;
+	use std::os::unix::io::AsRawFd;
+	use std::time::Instant;
+
+	use xcb;
+	use libc;
+
+	/// Waits for the next event, `None` once the deadline has passed.
+	pub fn poll(c: &xcb::Connection, deadline: Instant) -> Result<Option<xcb::GenericEvent>, xcb::ReplyError> {
+		c.flush();
+
+		loop {
+			if let Some(event) = c.poll_for_event() {
+				return Ok(Some(event));
+			}
+
+			if c.has_error().is_err() {
+				return Err(xcb::ReplyError::NullResponse);
+			}
+
+			let now = Instant::now();
+
+			if now >= deadline {
+				return Ok(None);
+			}
+
+			let timeout = cmp::min((deadline - now).as_millis(), i32::MAX as u128) as i32;
+			let mut fd  = libc::pollfd { fd: c.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+
+			unsafe {
+				libc::poll(&mut fd, 1, timeout);
+			}
+		}
+	}
+}
@@ -0,0 +1,672 @@
+use std::cmp;
+use std::time::{Duration, Instant};
+
+use xcb;
+use ewmh;
+
+/// The protocol version implemented.
+pub const VERSION: u32 = 5;
+
+/// The atoms used by the protocol.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Atoms {
+	pub aware:     xcb::Atom,
+	pub proxy:     xcb::Atom,
+	pub enter:     xcb::Atom,
+	pub position:  xcb::Atom,
+	pub status:    xcb::Atom,
+	pub leave:     xcb::Atom,
+	pub drop:      xcb::Atom,
+	pub finished:  xcb::Atom,
+	pub selection: xcb::Atom,
+	pub type_list: xcb::Atom,
+
+	pub action_copy:    xcb::Atom,
+	pub action_move:    xcb::Atom,
+	pub action_link:    xcb::Atom,
+	pub action_ask:     xcb::Atom,
+	pub action_private: xcb::Atom,
+	pub action_list:    xcb::Atom,
+}
+
+impl Atoms {
+	pub fn intern(c: &xcb::Connection) -> Result<Atoms, xcb::ReplyError> {
+		let names = ["XdndAware", "XdndProxy", "XdndEnter", "XdndPosition", "XdndStatus",
+			"XdndLeave", "XdndDrop", "XdndFinished", "XdndSelection", "XdndTypeList",
+			"XdndActionCopy", "XdndActionMove", "XdndActionLink", "XdndActionAsk",
+			"XdndActionPrivate", "XdndActionList"];
+
+		let cookies = names.iter()
+			.map(|name| xcb::intern_atom(c, false, name))
+			.collect::<Vec<_>>();
+
+		let mut atoms = Vec::with_capacity(cookies.len());
+		for cookie in cookies {
+			atoms.push(cookie.get_reply()?.atom());
+		}
+
+		Ok(Atoms {
+			aware:     atoms[0],
+			proxy:     atoms[1],
+			enter:     atoms[2],
+			position:  atoms[3],
+			status:    atoms[4],
+			leave:     atoms[5],
+			drop:      atoms[6],
+			finished:  atoms[7],
+			selection: atoms[8],
+			type_list: atoms[9],
+
+			action_copy:    atoms[10],
+			action_move:    atoms[11],
+			action_link:    atoms[12],
+			action_ask:     atoms[13],
+			action_private: atoms[14],
+			action_list:    atoms[15],
+		})
+	}
+}
+
+/// An XDND client message.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Message {
+	/// The pointer entered the target, if `more_types` is set the full list
+	/// is in the `XdndTypeList` of the source window.
+	Enter {
+		source:     xcb::Window,
+		version:    u32,
+		types:      Vec<xcb::Atom>,
+		more_types: bool,
+	},
+
+	/// The pointer moved, coordinates are relative to the root window.
+	Position {
+		source:    xcb::Window,
+		x:         i16,
+		y:         i16,
+		timestamp: xcb::Timestamp,
+		action:    xcb::Atom,
+	},
+
+	/// The target's answer to `Position`, no further position is sent while
+	/// the pointer stays in the rectangle unless `want_position` is set.
+	Status {
+		target:        xcb::Window,
+		accept:        bool,
+		want_position: bool,
+		rectangle:     (i16, i16, u16, u16),
+		action:        xcb::Atom,
+	},
+
+	Leave {
+		source: xcb::Window,
+	},
+
+	Drop {
+		source:    xcb::Window,
+		timestamp: xcb::Timestamp,
+	},
+
+	Finished {
+		target:   xcb::Window,
+		accepted: bool,
+		action:   xcb::Atom,
+	},
+}
+
+impl Message {
+	/// The message type and data.
+	pub fn encode(&self, atoms: &Atoms) -> (xcb::Atom, [u32; 5]) {
+		match *self {
+			Message::Enter { source, version, ref types, more_types } => {
+				let mut data = [source, version << 24 | more_types as u32, 0, 0, 0];
+
+				for (slot, &kind) in data[2 ..].iter_mut().zip(types.iter()) {
+					*slot = kind;
+				}
+
+				(atoms.enter, data)
+			}
+
+			Message::Position { source, x, y, timestamp, action } =>
+				(atoms.position, [source, 0, pack(x as u16, y as u16), timestamp, action]),
+
+			Message::Status { target, accept, want_position, rectangle: (x, y, width, height), action } =>
+				(atoms.status, [target, accept as u32 | (want_position as u32) << 1,
+					pack(x as u16, y as u16), pack(width, height), action]),
+
+			Message::Leave { source } =>
+				(atoms.leave, [source, 0, 0, 0, 0]),
+
+			Message::Drop { source, timestamp } =>
+				(atoms.drop, [source, 0, timestamp, 0, 0]),
+
+			Message::Finished { target, accepted, action } =>
+				(atoms.finished, [target, accepted as u32, action, 0, 0]),
+		}
+	}
+
+	pub fn decode(atoms: &Atoms, event: &xcb::ClientMessageEvent) -> Option<Message> {
+		if event.format() != 32 {
+			return None;
+		}
+
+		let kind = event.type_();
+		let data = event.data().data32();
+
+		Some(if kind == atoms.enter {
+			Message::Enter {
+				source:     data[0],
+				version:    data[1] >> 24,
+				types:      data[2 ..].iter().cloned().filter(|&t| t != xcb::NONE).collect(),
+				more_types: data[1] & 1 != 0,
+			}
+		}
+		else if kind == atoms.position {
+			Message::Position {
+				source:    data[0],
+				x:         (data[2] >> 16) as i16,
+				y:         data[2] as i16,
+				timestamp: data[3],
+				action:    data[4],
+			}
+		}
+		else if kind == atoms.status {
+			Message::Status {
+				target:        data[0],
+				accept:        data[1] & 1 != 0,
+				want_position: data[1] & 2 != 0,
+				rectangle:     ((data[2] >> 16) as i16, data[2] as i16, (data[3] >> 16) as u16, data[3] as u16),
+				action:        data[4],
+			}
+		}
+		else if kind == atoms.leave {
+			Message::Leave { source: data[0] }
+		}
+		else if kind == atoms.drop {
+			Message::Drop { source: data[0], timestamp: data[2] }
+		}
+		else if kind == atoms.finished {
+			Message::Finished { target: data[0], accepted: data[1] & 1 != 0, action: data[2] }
+		}
+		else {
+			return None;
+		})
+	}
+}
+
+fn pack(high: u16, low: u16) -> u32 {
+	(high as u32) << 16 | low as u32
+}
+
+/// Sends the message to the window, or to its proxy.
+pub fn send<'a>(c: &'a xcb::Connection, atoms: &Atoms, window: xcb::Window, proxy: Option<xcb::Window>, message: &Message) -> xcb::VoidCookie<'a> {
+	let (kind, data) = message.encode(atoms);
+	ewmh::send_client_message_direct(c, window, proxy.unwrap_or(window), kind, &data)
+}
+
+/// Marks the window as accepting drops.
+pub fn set_aware<'a>(c: &'a xcb::Connection, atoms: &Atoms, window: xcb::Window) -> xcb::VoidCookie<'a> {
+	xcb::change_property(c, xcb::PROP_MODE_REPLACE as u8, window, atoms.aware, xcb::ATOM_ATOM, 32, &[VERSION])
+}
+
+/// The XDND version supported by the window, if any.
+pub fn get_aware(c: &xcb::Connection, atoms: &Atoms, window: xcb::Window) -> Result<Option<u32>, xcb::ReplyError> {
+	let reply = xcb::get_property(c, false, window, atoms.aware, xcb::ATOM_ATOM, 0, 1).get_reply()?;
+	Ok(if reply.format() == 32 { reply.value::<u32>().first().cloned() } else { None })
+}
+
+/// The proxy of the window, only if the proxy points to itself as required.
+pub fn get_proxy(c: &xcb::Connection, atoms: &Atoms, window: xcb::Window) -> Result<Option<xcb::Window>, xcb::ReplyError> {
+	let get = |window| -> Result<Option<xcb::Window>, xcb::ReplyError> {
+		let reply = xcb::get_property(c, false, window, atoms.proxy, xcb::ATOM_WINDOW, 0, 1).get_reply()?;
+		Ok(if reply.format() == 32 { reply.value::<u32>().first().cloned() } else { None })
+	};
+
+	Ok(match get(window)? {
+		Some(proxy) if get(proxy).ok() == Some(Some(proxy)) =>
+			Some(proxy),
+
+		_ =>
+			None
+	})
+}
+
+/// Publishes the offered types, needed when there are more than three.
+pub fn set_type_list<'a>(c: &'a xcb::Connection, atoms: &Atoms, window: xcb::Window, types: &[xcb::Atom]) -> xcb::VoidCookie<'a> {
+	xcb::change_property(c, xcb::PROP_MODE_REPLACE as u8, window, atoms.type_list, xcb::ATOM_ATOM, 32, types)
+}
+
+pub fn get_type_list(c: &xcb::Connection, atoms: &Atoms, window: xcb::Window) -> Result<Vec<xcb::Atom>, xcb::ReplyError> {
+	let reply = xcb::get_property(c, false, window, atoms.type_list, xcb::ATOM_ATOM, 0, u32::MAX).get_reply()?;
+	Ok(if reply.format() == 32 { reply.value::<u32>().to_vec() } else { Vec::new() })
+}
+
+/// Publishes the actions offered for `XdndActionAsk`.
+pub fn set_action_list<'a>(c: &'a xcb::Connection, atoms: &Atoms, window: xcb::Window, actions: &[xcb::Atom]) -> xcb::VoidCookie<'a> {
+	xcb::change_property(c, xcb::PROP_MODE_REPLACE as u8, window, atoms.action_list, xcb::ATOM_ATOM, 32, actions)
+}
+
+pub fn get_action_list(c: &xcb::Connection, atoms: &Atoms, window: xcb::Window) -> Result<Vec<xcb::Atom>, xcb::ReplyError> {
+	let reply = xcb::get_property(c, false, window, atoms.action_list, xcb::ATOM_ATOM, 0, u32::MAX).get_reply()?;
+	Ok(if reply.format() == 32 { reply.value::<u32>().to_vec() } else { Vec::new() })
+}
+
+/// A window accepting drops.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Target {
+	pub window:  xcb::Window,
+	pub proxy:   Option<xcb::Window>,
+	pub version: u32,
+}
+
+/// Finds the XDND aware window under the given root coordinates, walking
+/// down from the topmost viewable window containing them.
+pub fn find_target(c: &xcb::Connection, atoms: &Atoms, root: xcb::Window, x: i16, y: i16) -> Result<Option<Target>, xcb::ReplyError> {
+	fn try_children(c: &xcb::Connection, atoms: &Atoms, window: xcb::Window, x: i32, y: i32) -> Result<Option<Target>, xcb::ReplyError> {
+		let query = xcb::query_tree(c, window).get_reply()?;
+
+		// Children are in bottom-to-top stacking order.
+		for &child in query.children().iter().rev() {
+			let attributes = xcb::get_window_attributes(c, child);
+			let geometry   = xcb::get_geometry(c, child);
+
+			let (attributes, geometry) = match (attributes.get_reply(), geometry.get_reply()) {
+				(Ok(attributes), Ok(geometry)) => (attributes, geometry),
+				_ => continue,
+			};
+
+			if attributes.map_state() != xcb::MAP_STATE_VIEWABLE as u8 {
+				continue;
+			}
+
+			let (cx, cy) = (x - geometry.x() as i32, y - geometry.y() as i32);
+			let border   = 2 * geometry.border_width() as i32;
+
+			if cx < 0 || cy < 0 || cx >= geometry.width() as i32 + border || cy >= geometry.height() as i32 + border {
+				continue;
+			}
+
+			if let Some(version) = get_aware(c, atoms, child)? {
+				return Ok(Some(Target {
+					window:  child,
+					proxy:   get_proxy(c, atoms, child)?,
+					version: version,
+				}));
+			}
+
+			// The topmost window under the pointer hides anything below it.
+			let border = geometry.border_width() as i32;
+			return try_children(c, atoms, child, cx - border, cy - border);
+		}
+
+		Ok(None)
+	}
+
+	try_children(c, atoms, root, x as i32, y as i32)
+}
+
+/// The state of a drag from the source side.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SourceState {
+	/// The pointer is not over an XDND aware window.
+	Idle,
+
+	/// The pointer is over a target.
+	Dragging,
+
+	/// The drop was sent and the target is transferring the data.
+	Dropped,
+
+	/// The target finished, or the drop could not happen.
+	Finished {
+		accepted: bool,
+		action:   xcb::Atom,
+	},
+}
+
+/// The source side of a drag.
+///
+/// The caller owns `XdndSelection` and answers the conversion requests of
+/// the target, this only takes care of the messages.
+pub struct Source<'a> {
+	conn:    &'a xcb::Connection,
+	atoms:   Atoms,
+	window:  xcb::Window,
+	types:   Vec<xcb::Atom>,
+	state:   SourceState,
+	target:  Option<Target>,
+	action:  Option<xcb::Atom>,
+	waiting: bool,
+	pending: Option<Message>,
+	drop:    Option<xcb::Timestamp>,
+	expires: Option<Instant>,
+}
+
+impl<'a> Source<'a> {
+	/// Starts a drag offering the given types from the window.
+	pub fn new(c: &'a xcb::Connection, atoms: &Atoms, window: xcb::Window, types: &[xcb::Atom]) -> Source<'a> {
+		if types.len() > 3 {
+			set_type_list(c, atoms, window, types);
+		}
+
+		Source {
+			conn:    c,
+			atoms:   *atoms,
+			window:  window,
+			types:   types.to_vec(),
+			state:   SourceState::Idle,
+			target:  None,
+			action:  None,
+			waiting: false,
+			pending: None,
+			drop:    None,
+			expires: None,
+		}
+	}
+
+	pub fn state(&self) -> SourceState {
+		self.state
+	}
+
+	pub fn target(&self) -> Option<Target> {
+		self.target
+	}
+
+	/// The action accepted by the target, if it accepts the drop.
+	pub fn action(&self) -> Option<xcb::Atom> {
+		self.action
+	}
+
+	/// Follows the pointer, entering and leaving targets as needed.
+	pub fn motion(&mut self, root: xcb::Window, x: i16, y: i16, timestamp: xcb::Timestamp, action: xcb::Atom) -> Result<(), xcb::ReplyError> {
+		match self.state {
+			SourceState::Idle | SourceState::Dragging => (),
+			_ => return Ok(()),
+		}
+
+		let target = find_target(self.conn, &self.atoms, root, x, y)?;
+
+		if target.map(|t| t.window) != self.target.map(|t| t.window) {
+			self.leave();
+
+			if let Some(target) = target {
+				self.send(target, Message::Enter {
+					source:     self.window,
+					version:    cmp::min(target.version, VERSION),
+					types:      self.types.iter().take(3).cloned().collect(),
+					more_types: self.types.len() > 3,
+				});
+
+				self.target = Some(target);
+				self.state  = SourceState::Dragging;
+			}
+		}
+
+		if let Some(target) = self.target {
+			let position = Message::Position {
+				source:    self.window,
+				x:         x,
+				y:         y,
+				timestamp: timestamp,
+				action:    action,
+			};
+
+			// Only one position can be in flight, the latest one is sent once
+			// the status arrives.
+			if self.waiting {
+				self.pending = Some(position);
+			}
+			else {
+				self.send(target, position);
+				self.waiting = true;
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Drops on the current target, returning `false` if nothing accepts it
+	/// and the drag is over.
+	///
+	/// The target has `timeout` to finish, see `expire`. Targets older than
+	/// version 2 never send `XdndFinished`, so the drag is finished as soon as
+	/// the drop is sent to them.
+	pub fn drop(&mut self, timestamp: xcb::Timestamp, timeout: Duration) -> bool {
+		let target = match (self.target, self.action) {
+			(Some(target), Some(_)) if self.state == SourceState::Dragging => target,
+
+			_ => {
+				self.cancel();
+				return false;
+			}
+		};
+
+		self.state   = SourceState::Dropped;
+		self.expires = Some(Instant::now() + timeout);
+
+		if self.waiting {
+			self.pending = None;
+			self.drop    = Some(timestamp);
+		}
+		else {
+			self.send_drop(target, timestamp);
+		}
+
+		true
+	}
+
+	/// When the target must have finished the drop by, if one is in progress.
+	pub fn deadline(&self) -> Option<Instant> {
+		match self.state {
+			SourceState::Dropped => self.expires,
+			_ => None,
+		}
+	}
+
+	/// Gives up on a drop the target didn't finish in time, returning whether
+	/// it did.
+	pub fn expire(&mut self, now: Instant) -> bool {
+		match self.deadline() {
+			Some(deadline) if now >= deadline => {
+				// The drop was sent, so there is nothing to leave.
+				self.target = None;
+				self.cancel();
+				true
+			}
+
+			_ =>
+				false
+		}
+	}
+
+	/// Aborts the drag.
+	pub fn cancel(&mut self) {
+		self.leave();
+		self.state = SourceState::Finished { accepted: false, action: xcb::NONE };
+	}
+
+	/// Handles a message sent to the source window, returning whether it was
+	/// one for this drag.
+	pub fn handle(&mut self, event: &xcb::ClientMessageEvent) -> bool {
+		if event.window() != self.window {
+			return false;
+		}
+
+		let target = match self.target {
+			Some(target) => target,
+			None => return false,
+		};
+
+		match Message::decode(&self.atoms, event) {
+			Some(Message::Status { target: window, accept, action, .. }) if window == target.window => {
+				self.waiting = false;
+				self.action  = if accept { Some(action) } else { None };
+
+				if let Some(timestamp) = self.drop.take() {
+					if accept {
+						self.send_drop(target, timestamp);
+					}
+					else {
+						self.cancel();
+					}
+				}
+				else if let Some(position) = self.pending.take() {
+					self.send(target, position);
+					self.waiting = true;
+				}
+
+				true
+			}
+
+			Some(Message::Finished { target: window, accepted, action }) if window == target.window => {
+				// Version 5 targets tell whether the drop succeeded, older ones
+				// only finish when it did.
+				let accepted = if target.version >= 5 { accepted } else { true };
+
+				self.state = SourceState::Finished {
+					accepted: accepted,
+					action:   if target.version >= 5 { action } else { self.action.unwrap_or(xcb::NONE) },
+				};
+
+				self.target = None;
+				true
+			}
+
+			_ =>
+				false
+		}
+	}
+
+	fn leave(&mut self) {
+		if let Some(target) = self.target.take() {
+			self.send(target, Message::Leave { source: self.window });
+		}
+
+		self.state   = SourceState::Idle;
+		self.action  = None;
+		self.waiting = false;
+		self.pending = None;
+		self.drop    = None;
+		self.expires = None;
+	}
+
+	fn send_drop(&mut self, target: Target, timestamp: xcb::Timestamp) {
+		self.send(target, Message::Drop { source: self.window, timestamp: timestamp });
+
+		if target.version < 2 {
+			self.state = SourceState::Finished {
+				accepted: true,
+				action:   self.action.unwrap_or(xcb::NONE),
+			};
+
+			self.target = None;
+		}
+	}
+
+	fn send(&self, target: Target, message: Message) {
+		send(self.conn, &self.atoms, target.window, target.proxy, &message);
+		self.conn.flush();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn atoms() -> Atoms {
+		Atoms {
+			aware:     1,
+			proxy:     2,
+			enter:     3,
+			position:  4,
+			status:    5,
+			leave:     6,
+			drop:      7,
+			finished:  8,
+			selection: 9,
+			type_list: 10,
+
+			action_copy:    11,
+			action_move:    12,
+			action_link:    13,
+			action_ask:     14,
+			action_private: 15,
+			action_list:    16,
+		}
+	}
+
+	fn round_trip(atoms: &Atoms, message: &Message) -> Option<Message> {
+		let (kind, data) = message.encode(atoms);
+		let event        = xcb::ClientMessageEvent::new(32, 42, kind, xcb::ClientMessageData::from_data32(data));
+
+		Message::decode(atoms, &event)
+	}
+
+	#[test]
+	fn enter() {
+		let atoms   = atoms();
+		let message = Message::Enter { source: 42, version: VERSION, types: vec![100, 101], more_types: true };
+
+		assert_eq!(message.encode(&atoms), (atoms.enter, [42, VERSION << 24 | 1, 100, 101, 0]));
+		assert_eq!(round_trip(&atoms, &message), Some(message));
+	}
+
+	#[test]
+	fn position() {
+		let atoms   = atoms();
+		let message = Message::Position { source: 42, x: -5, y: 7, timestamp: 1234, action: atoms.action_copy };
+
+		assert_eq!(message.encode(&atoms), (atoms.position, [42, 0, 0xfffb_0007, 1234, atoms.action_copy]));
+		assert_eq!(round_trip(&atoms, &message), Some(message));
+	}
+
+	#[test]
+	fn status() {
+		let atoms   = atoms();
+		let message = Message::Status {
+			target:        42,
+			accept:        true,
+			want_position: true,
+			rectangle:     (-1, 2, 300, 40),
+			action:        atoms.action_move,
+		};
+
+		assert_eq!(message.encode(&atoms), (atoms.status, [42, 3, 0xffff_0002, 300 << 16 | 40, atoms.action_move]));
+		assert_eq!(round_trip(&atoms, &message), Some(message));
+
+		let refused = Message::Status {
+			target:        42,
+			accept:        false,
+			want_position: false,
+			rectangle:     (0, 0, 0, 0),
+			action:        xcb::NONE,
+		};
+
+		assert_eq!(refused.encode(&atoms).1[1], 0);
+		assert_eq!(round_trip(&atoms, &refused), Some(refused));
+	}
+
+	#[test]
+	fn others() {
+		let atoms = atoms();
+
+		for message in &[
+			Message::Leave { source: 42 },
+			Message::Drop { source: 42, timestamp: 1234 },
+			Message::Finished { target: 42, accepted: true, action: atoms.action_copy },
+		] {
+			assert_eq!(round_trip(&atoms, message).as_ref(), Some(message));
+		}
+	}
+
+	#[test]
+	fn decode_foreign() {
+		let atoms = atoms();
+		let other = xcb::ClientMessageEvent::new(32, 42, atoms.aware, xcb::ClientMessageData::from_data32([0; 5]));
+		let bytes = xcb::ClientMessageEvent::new(8, 42, atoms.enter, xcb::ClientMessageData::from_data8([0; 20]));
+
+		assert_eq!(Message::decode(&atoms, &other), None);
+		assert_eq!(Message::decode(&atoms, &bytes), None);
+	}
+}
@@ -0,0 +1,449 @@
+use std::cmp;
+
+use xcb;
+
+/// The protocol version implemented.
+pub const VERSION: u32 = 0;
+
+pub type Flags = u32;
+pub const MAPPED: Flags = 1 << 0;
+
+pub type Opcode = u32;
+pub const EMBEDDED_NOTIFY:        Opcode = 0;
+pub const WINDOW_ACTIVATE:        Opcode = 1;
+pub const WINDOW_DEACTIVATE:      Opcode = 2;
+pub const REQUEST_FOCUS:          Opcode = 3;
+pub const FOCUS_IN:               Opcode = 4;
+pub const FOCUS_OUT:              Opcode = 5;
+pub const FOCUS_NEXT:             Opcode = 6;
+pub const FOCUS_PREV:             Opcode = 7;
+pub const MODALITY_ON:            Opcode = 10;
+pub const MODALITY_OFF:           Opcode = 11;
+pub const REGISTER_ACCELERATOR:   Opcode = 12;
+pub const UNREGISTER_ACCELERATOR: Opcode = 13;
+pub const ACTIVATE_ACCELERATOR:   Opcode = 14;
+
+pub type FocusDetail = u32;
+pub const FOCUS_CURRENT: FocusDetail = 0;
+pub const FOCUS_FIRST:   FocusDetail = 1;
+pub const FOCUS_LAST:    FocusDetail = 2;
+
+/// The atoms used by the protocol.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Atoms {
+	pub xembed:      xcb::Atom,
+	pub xembed_info: xcb::Atom,
+}
+
+impl Atoms {
+	pub fn intern(c: &xcb::Connection) -> Result<Atoms, xcb::ReplyError> {
+		let xembed      = xcb::intern_atom(c, false, "_XEMBED");
+		let xembed_info = xcb::intern_atom(c, false, "_XEMBED_INFO");
+
+		Ok(Atoms {
+			xembed:      xembed.get_reply()?.atom(),
+			xembed_info: xembed_info.get_reply()?.atom(),
+		})
+	}
+}
+
+/// The `_XEMBED_INFO` property of a client.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Info {
+	pub version: u32,
+	pub flags:   Flags,
+}
+
+impl Info {
+	pub fn is_mapped(&self) -> bool {
+		self.flags & MAPPED != 0
+	}
+}
+
+pub fn get_info(c: &xcb::Connection, atoms: &Atoms, window: xcb::Window) -> Result<Option<Info>, xcb::ReplyError> {
+	let reply = xcb::get_property(c, false, window, atoms.xembed_info, atoms.xembed_info, 0, 2).get_reply()?;
+	let value = reply.value::<u32>();
+
+	if reply.format() != 32 || value.len() < 2 {
+		return Ok(None);
+	}
+
+	Ok(Some(Info {
+		version: value[0],
+		flags:   value[1],
+	}))
+}
+
+pub fn set_info<'a>(c: &'a xcb::Connection, atoms: &Atoms, window: xcb::Window, info: Info) -> xcb::VoidCookie<'a> {
+	xcb::change_property(c, xcb::PROP_MODE_REPLACE as u8, window,
+		atoms.xembed_info, atoms.xembed_info, 32, &[info.version, info.flags])
+}
+
+/// An `_XEMBED` message.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Message {
+	/// Sent to the client once embedded.
+	EmbeddedNotify {
+		embedder: xcb::Window,
+		version:  u32,
+	},
+
+	WindowActivate,
+	WindowDeactivate,
+
+	/// Sent by the client to ask the embedder for focus.
+	RequestFocus,
+
+	FocusIn(FocusDetail),
+	FocusOut,
+
+	/// Sent by the client when focus leaves it going forward.
+	FocusNext,
+
+	/// Sent by the client when focus leaves it going backward.
+	FocusPrev,
+
+	ModalityOn,
+	ModalityOff,
+
+	RegisterAccelerator {
+		id:        u32,
+		keysym:    u32,
+		modifiers: u32,
+	},
+
+	UnregisterAccelerator {
+		id: u32,
+	},
+
+	ActivateAccelerator {
+		id:    u32,
+		flags: u32,
+	},
+
+	Other {
+		opcode: Opcode,
+		detail: u32,
+		data1:  u32,
+		data2:  u32,
+	},
+}
+
+impl Message {
+	/// The message as `[opcode, detail, data1, data2]`.
+	pub fn encode(&self) -> [u32; 4] {
+		match *self {
+			Message::EmbeddedNotify { embedder, version } => [EMBEDDED_NOTIFY, 0, embedder, version],
+			Message::WindowActivate                      => [WINDOW_ACTIVATE, 0, 0, 0],
+			Message::WindowDeactivate                    => [WINDOW_DEACTIVATE, 0, 0, 0],
+			Message::RequestFocus                        => [REQUEST_FOCUS, 0, 0, 0],
+			Message::FocusIn(detail)                     => [FOCUS_IN, detail, 0, 0],
+			Message::FocusOut                            => [FOCUS_OUT, 0, 0, 0],
+			Message::FocusNext                           => [FOCUS_NEXT, 0, 0, 0],
+			Message::FocusPrev                           => [FOCUS_PREV, 0, 0, 0],
+			Message::ModalityOn                          => [MODALITY_ON, 0, 0, 0],
+			Message::ModalityOff                         => [MODALITY_OFF, 0, 0, 0],
+
+			Message::RegisterAccelerator { id, keysym, modifiers } =>
+				[REGISTER_ACCELERATOR, id, keysym, modifiers],
+
+			Message::UnregisterAccelerator { id } =>
+				[UNREGISTER_ACCELERATOR, id, 0, 0],
+
+			Message::ActivateAccelerator { id, flags } =>
+				[ACTIVATE_ACCELERATOR, id, flags, 0],
+
+			Message::Other { opcode, detail, data1, data2 } =>
+				[opcode, detail, data1, data2],
+		}
+	}
+
+	pub fn decode(opcode: Opcode, detail: u32, data1: u32, data2: u32) -> Message {
+		match opcode {
+			EMBEDDED_NOTIFY        => Message::EmbeddedNotify { embedder: data1, version: data2 },
+			WINDOW_ACTIVATE        => Message::WindowActivate,
+			WINDOW_DEACTIVATE      => Message::WindowDeactivate,
+			REQUEST_FOCUS          => Message::RequestFocus,
+			FOCUS_IN               => Message::FocusIn(detail),
+			FOCUS_OUT              => Message::FocusOut,
+			FOCUS_NEXT             => Message::FocusNext,
+			FOCUS_PREV             => Message::FocusPrev,
+			MODALITY_ON            => Message::ModalityOn,
+			MODALITY_OFF           => Message::ModalityOff,
+			REGISTER_ACCELERATOR   => Message::RegisterAccelerator { id: detail, keysym: data1, modifiers: data2 },
+			UNREGISTER_ACCELERATOR => Message::UnregisterAccelerator { id: detail },
+			ACTIVATE_ACCELERATOR   => Message::ActivateAccelerator { id: detail, flags: data1 },
+
+			opcode =>
+				Message::Other { opcode: opcode, detail: detail, data1: data1, data2: data2 },
+		}
+	}
+}
+
+/// Sends the message to the window, messages go directly to the window
+/// without any event mask as the specification requires.
+pub fn send<'a>(c: &'a xcb::Connection, atoms: &Atoms, window: xcb::Window, timestamp: xcb::Timestamp, message: Message) -> xcb::VoidCookie<'a> {
+	let [opcode, detail, data1, data2] = message.encode();
+	let data  = xcb::ClientMessageData::from_data32([timestamp, opcode, detail, data1, data2]);
+	let event = xcb::ClientMessageEvent::new(32, window, atoms.xembed, data);
+
+	xcb::send_event(c, false, window, xcb::EVENT_MASK_NO_EVENT, &event)
+}
+
+/// Decodes an `_XEMBED` client message into its timestamp and message.
+pub fn decode(atoms: &Atoms, event: &xcb::ClientMessageEvent) -> Option<(xcb::Timestamp, Message)> {
+	if event.type_() != atoms.xembed || event.format() != 32 {
+		return None;
+	}
+
+	let data = event.data().data32();
+	Some((data[0], Message::decode(data[1], data[2], data[3], data[4])))
+}
+
+/// The embedder side, holding a client reparented into a socket window.
+pub struct Embedder<'a> {
+	conn:    &'a xcb::Connection,
+	atoms:   Atoms,
+	socket:  xcb::Window,
+	client:  xcb::Window,
+	version: u32,
+	mapped:  bool,
+}
+
+impl<'a> Embedder<'a> {
+	/// Embeds the client in the socket window following the specification:
+	/// the client is reparented, notified, and mapped if it asks to be.
+	///
+	/// Clients without `_XEMBED_INFO` are treated as wanting to be mapped.
+	pub fn embed(c: &'a xcb::Connection, atoms: &Atoms, socket: xcb::Window, client: xcb::Window, timestamp: xcb::Timestamp) -> Result<Embedder<'a>, xcb::ReplyError> {
+		let info = get_info(c, atoms, client)?.unwrap_or(Info { version: VERSION, flags: MAPPED });
+
+		xcb::change_window_attributes(c, client, &[
+			(xcb::CW_EVENT_MASK, xcb::EVENT_MASK_PROPERTY_CHANGE | xcb::EVENT_MASK_STRUCTURE_NOTIFY)]);
+		xcb::reparent_window_checked(c, client, socket, 0, 0).request_check()?;
+
+		let mut embedder = Embedder {
+			conn:    c,
+			atoms:   *atoms,
+			socket:  socket,
+			client:  client,
+			version: cmp::min(info.version, VERSION),
+			mapped:  false,
+		};
+
+		embedder.send(timestamp, Message::EmbeddedNotify { embedder: socket, version: embedder.version });
+		embedder.update(info);
+
+		Ok(embedder)
+	}
+
+	pub fn socket(&self) -> xcb::Window {
+		self.socket
+	}
+
+	pub fn client(&self) -> xcb::Window {
+		self.client
+	}
+
+	/// The negotiated protocol version.
+	pub fn version(&self) -> u32 {
+		self.version
+	}
+
+	pub fn is_mapped(&self) -> bool {
+		self.mapped
+	}
+
+	pub fn send(&self, timestamp: xcb::Timestamp, message: Message) -> xcb::VoidCookie<'a> {
+		send(self.conn, &self.atoms, self.client, timestamp, message)
+	}
+
+	/// Follows changes to the `_XEMBED_INFO` of the client, mapping or
+	/// unmapping it as requested. Returns whether the event was handled.
+	pub fn handle_property(&mut self, event: &xcb::PropertyNotifyEvent) -> Result<bool, xcb::ReplyError> {
+		if event.window() != self.client || event.atom() != self.atoms.xembed_info {
+			return Ok(false);
+		}
+
+		let info = get_info(self.conn, &self.atoms, self.client)?
+			.unwrap_or(Info { version: self.version, flags: MAPPED });
+		self.update(info);
+
+		Ok(true)
+	}
+
+	/// Reparents the client back to the given window, usually the root, and
+	/// unmaps it.
+	pub fn release(self, parent: xcb::Window) -> xcb::VoidCookie<'a> {
+		xcb::unmap_window(self.conn, self.client);
+		xcb::reparent_window(self.conn, self.client, parent, 0, 0)
+	}
+
+	fn update(&mut self, info: Info) {
+		if info.is_mapped() && !self.mapped {
+			xcb::map_window(self.conn, self.client);
+		}
+		else if !info.is_mapped() && self.mapped {
+			xcb::unmap_window(self.conn, self.client);
+		}
+
+		self.mapped = info.is_mapped();
+	}
+}
+
+/// The client side, tracking the state the embedder communicates.
+pub struct Client<'a> {
+	conn:     &'a xcb::Connection,
+	atoms:    Atoms,
+	window:   xcb::Window,
+	embedder: Option<xcb::Window>,
+	active:   bool,
+	focused:  bool,
+	modal:    bool,
+}
+
+impl<'a> Client<'a> {
+	/// Publishes `_XEMBED_INFO` on the window so it can be embedded.
+	pub fn new(c: &'a xcb::Connection, atoms: &Atoms, window: xcb::Window, mapped: bool) -> Client<'a> {
+		set_info(c, atoms, window, Info { version: VERSION, flags: if mapped { MAPPED } else { 0 } });
+
+		Client {
+			conn:     c,
+			atoms:    *atoms,
+			window:   window,
+			embedder: None,
+			active:   false,
+			focused:  false,
+			modal:    false,
+		}
+	}
+
+	pub fn window(&self) -> xcb::Window {
+		self.window
+	}
+
+	pub fn embedder(&self) -> Option<xcb::Window> {
+		self.embedder
+	}
+
+	pub fn is_active(&self) -> bool {
+		self.active
+	}
+
+	pub fn is_focused(&self) -> bool {
+		self.focused
+	}
+
+	pub fn is_modal(&self) -> bool {
+		self.modal
+	}
+
+	/// Asks the embedder to map or unmap the window.
+	pub fn set_mapped(&self, mapped: bool) -> xcb::VoidCookie<'a> {
+		set_info(self.conn, &self.atoms, self.window, Info { version: VERSION, flags: if mapped { MAPPED } else { 0 } })
+	}
+
+	/// Decodes a message sent to the window and updates the state.
+	pub fn handle(&mut self, event: &xcb::ClientMessageEvent) -> Option<Message> {
+		if event.window() != self.window {
+			return None;
+		}
+
+		let (_, message) = decode(&self.atoms, event)?;
+
+		match message {
+			Message::EmbeddedNotify { embedder, .. } =>
+				self.embedder = Some(embedder),
+
+			Message::WindowActivate   => self.active = true,
+			Message::WindowDeactivate => self.active = false,
+			Message::FocusIn(..)      => self.focused = true,
+			Message::FocusOut         => self.focused = false,
+			Message::ModalityOn       => self.modal = true,
+			Message::ModalityOff      => self.modal = false,
+
+			_ => ()
+		}
+
+		Some(message)
+	}
+
+	/// Forgets the embedder, to be called when the window is reparented away
+	/// from it.
+	pub fn unembedded(&mut self) {
+		self.embedder = None;
+		self.active   = false;
+		self.focused  = false;
+		self.modal    = false;
+	}
+
+	/// Sends a message to the embedder, if embedded.
+	pub fn send(&self, timestamp: xcb::Timestamp, message: Message) -> Option<xcb::VoidCookie<'a>> {
+		self.embedder.map(|embedder| send(self.conn, &self.atoms, embedder, timestamp, message))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn atoms() -> Atoms {
+		Atoms {
+			xembed:      1,
+			xembed_info: 2,
+		}
+	}
+
+	fn event(atoms: &Atoms, timestamp: xcb::Timestamp, message: Message) -> xcb::ClientMessageEvent {
+		let [opcode, detail, data1, data2] = message.encode();
+
+		xcb::ClientMessageEvent::new(32, 42, atoms.xembed,
+			xcb::ClientMessageData::from_data32([timestamp, opcode, detail, data1, data2]))
+	}
+
+	#[test]
+	fn encode() {
+		assert_eq!(Message::EmbeddedNotify { embedder: 7, version: 0 }.encode(), [EMBEDDED_NOTIFY, 0, 7, 0]);
+		assert_eq!(Message::FocusIn(FOCUS_LAST).encode(), [FOCUS_IN, FOCUS_LAST, 0, 0]);
+		assert_eq!(Message::RegisterAccelerator { id: 1, keysym: 2, modifiers: 3 }.encode(),
+			[REGISTER_ACCELERATOR, 1, 2, 3]);
+		assert_eq!(Message::ActivateAccelerator { id: 4, flags: 5 }.encode(), [ACTIVATE_ACCELERATOR, 4, 5, 0]);
+	}
+
+	#[test]
+	fn round_trip() {
+		let atoms    = atoms();
+		let messages = [
+			Message::EmbeddedNotify { embedder: 7, version: VERSION },
+			Message::WindowActivate,
+			Message::WindowDeactivate,
+			Message::RequestFocus,
+			Message::FocusIn(FOCUS_FIRST),
+			Message::FocusOut,
+			Message::FocusNext,
+			Message::FocusPrev,
+			Message::ModalityOn,
+			Message::ModalityOff,
+			Message::RegisterAccelerator { id: 1, keysym: 0xff0d, modifiers: 4 },
+			Message::UnregisterAccelerator { id: 1 },
+			Message::ActivateAccelerator { id: 1, flags: 0 },
+			Message::Other { opcode: 99, detail: 1, data1: 2, data2: 3 },
+		];
+
+		for (i, message) in messages.iter().enumerate() {
+			assert_eq!(decode(&atoms, &event(&atoms, i as u32, *message)), Some((i as u32, *message)));
+		}
+	}
+
+	#[test]
+	fn decode_foreign() {
+		let atoms = atoms();
+		let other = xcb::ClientMessageEvent::new(32, 42, atoms.xembed_info,
+			xcb::ClientMessageData::from_data32([0, WINDOW_ACTIVATE, 0, 0, 0]));
+		let bytes = xcb::ClientMessageEvent::new(8, 42, atoms.xembed,
+			xcb::ClientMessageData::from_data8([0; 20]));
+
+		assert_eq!(decode(&atoms, &other), None);
+		assert_eq!(decode(&atoms, &bytes), None);
+	}
+}